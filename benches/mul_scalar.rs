@@ -0,0 +1,53 @@
+//! Compare `Point::mul_scalar` with the previous least significant bit first
+//! double-and-add, which cloned the point and doubled a copy of it.
+//!
+//! Run with `cargo bench --bench mul_scalar`.
+
+use std::time::{Duration, Instant};
+
+use num_bigint::BigUint;
+use num_traits::Zero;
+use oxicoin::secp256k1::curve::Point;
+use oxicoin::secp256k1::{generator, order};
+
+const ITERATIONS: u32 = 50;
+
+fn lsb_first(point: &Point, k: &BigUint) -> Point {
+    let mut coef = k % order();
+    let mut result = Point::zero();
+    let mut current = point.clone();
+
+    while !coef.is_zero() {
+        if coef.bit(0) {
+            result = &result + &current;
+        }
+
+        coef >>= 1;
+        current = &current + &current;
+    }
+
+    result
+}
+
+fn time<F: FnMut() -> Point>(mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        std::hint::black_box(f());
+    }
+
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    let k = order() - 12345usize;
+    let other = generator().mul_scalar(&BigUint::from(7usize));
+
+    for (name, point) in [("G", generator()), ("7G", &other)] {
+        let borrowing = time(|| point.mul_scalar(&k));
+        let previous = time(|| lsb_first(point, &k));
+        println!(
+            "{}: mul_scalar {:?}, lsb first {:?}",
+            name, borrowing, previous
+        );
+    }
+}
@@ -4,37 +4,75 @@ use num_integer::Integer;
 use num_traits::{ToPrimitive, Zero};
 
 use crate::utils::hash256;
+use crate::{Error, Result};
 
-const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+/// Alphabet used by Bitcoin, the default one
+pub const BASE58_ALPHABET: &[u8; 58] =
+    b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
 
 pub fn encode<B>(bytes: B) -> String
 where
     B: AsRef<[u8]>,
 {
+    encode_unchecked(bytes.as_ref(), BASE58_ALPHABET)
+}
+
+/// Encode with a custom alphabet (e.g. Ripple's), its first character
+/// stands for the leading zeroes
+pub fn encode_with_alphabet<B>(bytes: B, alphabet: &[u8; 58]) -> Result<String>
+where
+    B: AsRef<[u8]>,
+{
+    check_alphabet(alphabet)?;
+    Ok(encode_unchecked(bytes.as_ref(), alphabet))
+}
+
+fn check_alphabet(alphabet: &[u8; 58]) -> Result<()> {
+    let distinct = alphabet
+        .iter()
+        .enumerate()
+        .all(|(i, c)| c.is_ascii_graphic() && !alphabet[..i].contains(c));
+
+    if distinct {
+        Ok(())
+    } else {
+        Err(Error::InvalidBase58Alphabet)
+    }
+}
+
+fn encode_unchecked(bytes: &[u8], alphabet: &[u8; 58]) -> String {
     lazy_static! {
         static ref BASE: BigUint = BigUint::from(58usize);
     }
 
-    let bytes = bytes.as_ref();
     let zeroes_count = bytes.iter().take_while(|b| **b == 0).count();
-    let prefix = String::from_utf8(vec![b'1'; zeroes_count]).unwrap();
+    let prefix = String::from_utf8(vec![alphabet[0]; zeroes_count]).unwrap();
     let mut number = BigUint::from_bytes_be(bytes);
 
     let mut result = String::new();
     while !number.is_zero() {
         let (q, r) = number.div_mod_floor(&*BASE);
         number = q;
-        result.push(BASE58_ALPHABET[r.to_usize().unwrap()] as char);
+        result.push(alphabet[r.to_usize().unwrap()] as char);
     }
 
+    // digits are pushed least significant first, so the zero characters for
+    // the leading zeroes go last and end up in front once reversed
     result.push_str(&prefix);
     result.chars().rev().collect()
 }
 
-pub fn encode_checksum<B>(bytes: B) -> String
+/// Encode the given bytes followed by their checksum, the payload can't be
+/// empty since the result would be just a checksum (unlike [`encode`], which
+/// encodes no bytes as an empty string)
+pub fn encode_checksum<B>(bytes: B) -> Result<String>
 where
     B: AsRef<[u8]>,
 {
+    if bytes.as_ref().is_empty() {
+        return Err(Error::EmptyBase58Payload);
+    }
+
     let checksum = hash256(bytes.as_ref());
     let data: Vec<_> = bytes
         .as_ref()
@@ -42,7 +80,62 @@ where
         .chain(&checksum[..4])
         .copied()
         .collect();
-    encode(&data)
+    Ok(encode(&data))
+}
+
+pub fn decode<S>(string: S) -> Result<Vec<u8>>
+where
+    S: AsRef<str>,
+{
+    decode_unchecked(string.as_ref(), BASE58_ALPHABET)
+}
+
+/// Decode with a custom alphabet, see [`encode_with_alphabet`]
+pub fn decode_with_alphabet<S>(string: S, alphabet: &[u8; 58]) -> Result<Vec<u8>>
+where
+    S: AsRef<str>,
+{
+    check_alphabet(alphabet)?;
+    decode_unchecked(string.as_ref(), alphabet)
+}
+
+fn decode_unchecked(string: &str, alphabet: &[u8; 58]) -> Result<Vec<u8>> {
+    let mut number = BigUint::zero();
+    for c in string.chars() {
+        let digit = alphabet
+            .iter()
+            .position(|b| *b as char == c)
+            .ok_or(Error::InvalidBase58Character(c))?;
+
+        number = number * 58usize + digit;
+    }
+
+    let zero = alphabet[0] as char;
+    let zeroes_count = string.chars().take_while(|c| *c == zero).count();
+    let mut result = vec![0u8; zeroes_count];
+    if !number.is_zero() {
+        result.append(&mut number.to_bytes_be());
+    }
+
+    Ok(result)
+}
+
+/// Decode the given string and verify its checksum, which is stripped
+pub fn decode_checksum<S>(string: S) -> Result<Vec<u8>>
+where
+    S: AsRef<str>,
+{
+    let mut data = decode(string)?;
+    if data.len() < 4 {
+        return Err(Error::InvalidBase58Checksum);
+    }
+
+    let checksum = data.split_off(data.len() - 4);
+    if hash256(&data)[..4] != checksum[..] {
+        return Err(Error::InvalidBase58Checksum);
+    }
+
+    Ok(data)
 }
 
 #[cfg(test)]
@@ -64,4 +157,73 @@ mod tests {
         let expected = "EQJsjkd6JaGwxrjEhfeqPenqHwrBmPQZjJGNSCHBkcF7";
         assert_eq!(encode(input), expected.to_string());
     }
+
+    #[test]
+    fn encode_base58_leading_zeroes() {
+        assert_eq!(encode(hex!("0000287fb4cd")), "11233QC4");
+        assert_eq!(encode([0u8; 10]), "1111111111");
+        assert_eq!(decode("11233QC4").unwrap(), hex!("0000287fb4cd").to_vec());
+    }
+
+    #[test]
+    fn decode_base58() {
+        let input = "4fE3H2E6XMp4SsxtwinF7w9a34ooUrwWe4WsW1458Pd";
+        let expected = hex!("eff69ef2b1bd93a66ed5219add4fb51e11a840f404876325a1e8ffe0529a2c");
+        assert_eq!(decode(input).unwrap(), expected.to_vec());
+
+        assert!(decode("0OIl").is_err());
+    }
+
+    #[test]
+    fn decode_base58_checksum() {
+        let data = hex!("6f3a1f3bfe9fa5b6aa0d0a7ae63e3a5e2fd6a5d5e6");
+        let encoded = encode_checksum(data).unwrap();
+        assert_eq!(decode_checksum(&encoded).unwrap(), data.to_vec());
+
+        let mut tampered = encoded.into_bytes();
+        tampered[5] = if tampered[5] == b'2' { b'3' } else { b'2' };
+        let tampered = String::from_utf8(tampered).unwrap();
+        assert!(decode_checksum(tampered).is_err());
+    }
+
+    #[test]
+    fn empty_payloads() {
+        assert_eq!(encode([]), "");
+        assert_eq!(decode("").unwrap(), Vec::<u8>::new());
+
+        assert!(matches!(
+            encode_checksum([]),
+            Err(Error::EmptyBase58Payload)
+        ));
+        assert!(encode_checksum([0x00]).is_ok());
+    }
+
+    #[test]
+    fn custom_alphabet() -> Result<()> {
+        let ripple = b"rpshnaf39wBUDNEGHJKLM4PQRST7VWXYZ2bcdeCg65jkm8oFqi1tuvAxyz";
+        let data = hex!("00000b6cd9b4c4e04b5c5e2e4fbb5c9ffa5e9b66c6");
+
+        let encoded = encode_with_alphabet(data, ripple)?;
+        assert!(encoded.starts_with("rr"));
+        assert_ne!(encoded, encode(data));
+        assert_eq!(decode_with_alphabet(&encoded, ripple)?, data.to_vec());
+
+        // the default alphabet behaves just like the plain functions
+        let encoded = encode_with_alphabet(data, BASE58_ALPHABET)?;
+        assert_eq!(encoded, encode(data));
+        assert_eq!(
+            decode_with_alphabet(&encoded, BASE58_ALPHABET)?,
+            data.to_vec()
+        );
+
+        let mut repeated = *BASE58_ALPHABET;
+        repeated[1] = b'1';
+        assert!(matches!(
+            encode_with_alphabet(data, &repeated),
+            Err(Error::InvalidBase58Alphabet)
+        ));
+        assert!(decode_with_alphabet("11", &repeated).is_err());
+
+        Ok(())
+    }
 }
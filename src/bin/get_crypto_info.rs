@@ -1,7 +1,8 @@
 use std::env;
 
 use anyhow::{anyhow, Result};
-use oxicoin::secp256k1::crypto::PrivateKey;
+use oxicoin::network::Network;
+use oxicoin::secp256k1::crypto::{KeyPair, PrivateKey};
 use oxicoin::utils::hash256;
 
 fn main() -> Result<()> {
@@ -13,13 +14,18 @@ fn main() -> Result<()> {
     }
 
     let secret_digest = hash256(&args[1]);
-    let private_key = PrivateKey::from_bytes_be(secret_digest);
-    let public_key = private_key.public_key();
+    let key_pair = KeyPair::from(PrivateKey::from_bytes_be(secret_digest)?);
 
-    println!("Main address: {:?}", public_key.create_address(true, false));
-    println!("Test address: {:?}", public_key.create_address(true, true));
-    println!("Main WIF: {:?}", private_key.create_wif(true, false));
-    println!("Test WIF: {:?}", private_key.create_wif(true, true));
+    println!(
+        "Main address: {:?}",
+        key_pair.address(true, Network::Mainnet)
+    );
+    println!(
+        "Test address: {:?}",
+        key_pair.address(true, Network::Testnet)
+    );
+    println!("Main WIF: {:?}", key_pair.wif(true, Network::Mainnet));
+    println!("Test WIF: {:?}", key_pair.wif(true, Network::Testnet));
 
     Ok(())
 }
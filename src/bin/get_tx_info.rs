@@ -0,0 +1,41 @@
+//! Print information about a raw transaction read (as hex) from stdin.
+//!
+//! Usage: `echo <tx hex> | get_tx_info [--testnet] [--fee]`, the fee is only
+//! computed when asked for since it fetches the previous transactions (from
+//! the server in `OXICOIN_FETCHER_URL`, if set).
+
+use std::env;
+use std::io::{self, Read};
+
+use anyhow::{anyhow, Result};
+use oxicoin::core::tx::Tx;
+
+fn main() -> Result<()> {
+    let args: Vec<_> = env::args().skip(1).collect();
+    let testnet = args.iter().any(|arg| arg == "--testnet");
+    let with_fee = args.iter().any(|arg| arg == "--fee");
+
+    if args.iter().any(|arg| arg != "--testnet" && arg != "--fee") {
+        println!("Usage: program [--testnet] [--fee] < tx.hex");
+        return Err(anyhow!("Invalid args"));
+    }
+
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    let bytes = hex::decode(input.trim())?;
+    let tx = Tx::deserialize(bytes.as_slice(), testnet)?;
+
+    println!("Id: {}", tx.id()?);
+    println!("Inputs: {}", tx.inputs().len());
+    println!("Outputs: {}", tx.outputs().len());
+
+    if with_fee {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        println!("Fee: {}", runtime.block_on(tx.fee(testnet))?);
+    }
+
+    Ok(())
+}
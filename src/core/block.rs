@@ -0,0 +1,320 @@
+use std::convert::TryInto;
+use std::future::Future;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use bytes::{Buf, Bytes};
+use derivative::Derivative;
+use num_bigint::BigUint;
+
+use crate::utils::{hash256, read_u256_le, reverse_bytes, write_u256_le};
+use crate::{Error, Result};
+
+use super::fetcher::TX_FETCHER;
+use super::tx::Tx;
+
+#[derive(Derivative, Clone, PartialEq, Eq)]
+#[derivative(Debug)]
+pub struct BlockHeader {
+    pub(crate) version: u32,
+    #[derivative(Debug(format_with = "crate::format::bytes::fmt"))]
+    pub(crate) prev_block: Bytes, // size: 32 bytes
+    #[derivative(Debug(format_with = "crate::format::bytes::fmt"))]
+    pub(crate) merkle_root: Bytes, // size: 32 bytes
+    pub(crate) timestamp: u32,
+    pub(crate) bits: u32,
+    pub(crate) nonce: u32,
+}
+
+impl BlockHeader {
+    pub fn id(&self) -> Result<String> {
+        Ok(hex::encode(self.hash()?))
+    }
+
+    pub fn hash(&self) -> Result<Vec<u8>> {
+        let serialized = self.serialize()?;
+        Ok(reverse_bytes(&hash256(&serialized)))
+    }
+
+    /// Get the target encoded in `bits`, a valid block hash must be below it
+    pub fn target(&self) -> BigUint {
+        let exponent = self.bits >> 24;
+        let coefficient = BigUint::from(self.bits & 0x00ff_ffff);
+
+        if exponent < 3 {
+            coefficient >> (8 * (3 - exponent) as usize)
+        } else {
+            coefficient << (8 * (exponent - 3) as usize)
+        }
+    }
+
+    /// Check whether the hash of this header is below its target
+    pub fn check_pow(&self) -> Result<bool> {
+        let proof = BigUint::from_bytes_be(&self.hash()?);
+        Ok(proof < self.target())
+    }
+
+    /// Check whether this header builds on top of `prev`
+    pub fn connects_to(&self, prev: &BlockHeader) -> Result<bool> {
+        Ok(self.prev_block.as_ref() == prev.hash()?.as_slice())
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        let version_bytes = self.version.to_le_bytes();
+        let prev_block_bytes = write_u256_le(Self::as_u256(&self.prev_block)?);
+        let merkle_root_bytes = write_u256_le(Self::as_u256(&self.merkle_root)?);
+        let timestamp_bytes = self.timestamp.to_le_bytes();
+        let bits_bytes = self.bits.to_le_bytes();
+        let nonce_bytes = self.nonce.to_le_bytes();
+
+        let result = version_bytes
+            .iter()
+            .chain(prev_block_bytes.iter())
+            .chain(merkle_root_bytes.iter())
+            .chain(timestamp_bytes.iter())
+            .chain(bits_bytes.iter())
+            .chain(nonce_bytes.iter())
+            .copied()
+            .collect();
+
+        Ok(result)
+    }
+
+    pub fn deserialize(buf: impl Buf) -> Result<Self> {
+        let mut reader = buf.reader();
+
+        let version = reader.read_u32::<LittleEndian>()?;
+        let prev_block = Bytes::copy_from_slice(&read_u256_le(&mut reader)?);
+        let merkle_root = Bytes::copy_from_slice(&read_u256_le(&mut reader)?);
+        let timestamp = reader.read_u32::<LittleEndian>()?;
+        let bits = reader.read_u32::<LittleEndian>()?;
+        let nonce = reader.read_u32::<LittleEndian>()?;
+
+        Ok(Self {
+            version,
+            prev_block,
+            merkle_root,
+            timestamp,
+            bits,
+            nonce,
+        })
+    }
+
+    fn as_u256(bytes: &Bytes) -> Result<&[u8; 32]> {
+        bytes
+            .as_ref()
+            .try_into()
+            .map_err(|_| Error::InvalidBlockHeader("hashes must be 32 bytes"))
+    }
+}
+
+/// Validate a run of consecutive headers, each one must satisfy its proof of
+/// work and build on top of the previous one.
+pub fn validate_chain(headers: &[BlockHeader]) -> Result<()> {
+    for header in headers {
+        if !header.check_pow()? {
+            return Err(Error::InvalidBlockHeader("bad proof of work"));
+        }
+    }
+
+    for pair in headers.windows(2) {
+        if !pair[1].connects_to(&pair[0])? {
+            return Err(Error::InvalidBlockHeader("doesn't connect to previous"));
+        }
+    }
+
+    Ok(())
+}
+
+/// A block header along with its transactions, the first one being the
+/// coinbase
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub(crate) header: BlockHeader,
+    pub(crate) txs: Vec<Tx>,
+}
+
+impl Block {
+    pub fn new(header: BlockHeader, txs: Vec<Tx>) -> Self {
+        Self { header, txs }
+    }
+
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    pub fn txs(&self) -> &[Tx] {
+        &self.txs
+    }
+
+    /// Check that the coinbase (and only the first transaction) is one and that
+    /// its outputs don't claim more than the `subsidy` plus the fees of all the
+    /// other transactions, whose previous transactions are fetched
+    pub async fn validate_coinbase(&self, subsidy: u64, testnet: bool) -> Result<bool> {
+        self.validate_coinbase_with(subsidy, move |tx_id| async move {
+            TX_FETCHER.fetch(&tx_id, testnet, false).await
+        })
+        .await
+    }
+
+    async fn validate_coinbase_with<F, Fut>(&self, subsidy: u64, fetch: F) -> Result<bool>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<Tx>> + Send + 'static,
+    {
+        let (coinbase, txs) = match self.txs.split_first() {
+            Some((coinbase, txs)) if coinbase.is_coinbase() => (coinbase, txs),
+            _ => return Ok(false),
+        };
+
+        if txs.iter().any(Tx::is_coinbase) {
+            return Ok(false);
+        }
+
+        let mut reward = subsidy;
+        for tx in txs {
+            reward = reward
+                .checked_add(tx.fee_with(&fetch).await?)
+                .ok_or(Error::AmountOverflow)?;
+        }
+
+        Ok(coinbase.total_output_value()? <= reward)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use hex_literal::hex;
+
+    use super::*;
+
+    fn first_mainnet_headers() -> Result<Vec<BlockHeader>> {
+        let raw_headers = [
+            hex!(
+                "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd
+                7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c"
+            ),
+            hex!(
+                "010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd
+                1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299"
+            ),
+            hex!(
+                "010000004860eb18bf1b1620e37e9490fc8a427514416fd75159ab86688e9a8300000000d5fdcc54
+                1e25de1c7a5addedf24858b8bb665c9f36ef744ee42c316022c90f9bb0bc6649ffff001d08d2bd61"
+            ),
+        ];
+
+        let headers = raw_headers
+            .iter()
+            .map(|raw| BlockHeader::deserialize(&raw[..]))
+            .collect::<crate::Result<_>>()?;
+
+        Ok(headers)
+    }
+
+    #[test]
+    fn header_roundtrip() -> Result<()> {
+        let headers = first_mainnet_headers()?;
+        let genesis = &headers[0];
+
+        assert_eq!(
+            genesis.id()?,
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        assert_eq!(genesis.serialize()?.len(), 80);
+        assert_eq!(
+            BlockHeader::deserialize(&genesis.serialize()?[..])?,
+            *genesis
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn coinbase_reward() -> Result<()> {
+        use std::collections::HashMap;
+        use std::sync::Arc;
+
+        use crate::core::input::Input;
+        use crate::core::output::Output;
+        use crate::core::script::Script;
+        use crate::core::tx::TxBuilder;
+
+        let output = |amount| Output {
+            amount,
+            script_pubkey: Script::new(),
+        };
+
+        let (a, b) = ([0x0a; 32], [0x0b; 32]);
+        let prev_txs: HashMap<_, _> = vec![
+            (
+                hex::encode(a),
+                TxBuilder::new(false).output(output(1000)).build(),
+            ),
+            (
+                hex::encode(b),
+                TxBuilder::new(false).output(output(500)).build(),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let prev_txs = Arc::new(prev_txs);
+
+        let fetch = |tx_id: String| {
+            let prev_txs = prev_txs.clone();
+            async move { Ok(prev_txs[&tx_id].clone()) }
+        };
+
+        // fees of 100 and 50
+        let txs = vec![
+            TxBuilder::new(false)
+                .input(Input::new(a, 0)?)
+                .output(output(900))
+                .build(),
+            TxBuilder::new(false)
+                .input(Input::new(b, 0)?)
+                .output(output(450))
+                .build(),
+        ];
+
+        let header = first_mainnet_headers()?.remove(0);
+        let block = |claimed| -> Result<Block> {
+            let coinbase = Tx::coinbase(1, vec![output(claimed)], None)?;
+            let all_txs = std::iter::once(coinbase).chain(txs.clone()).collect();
+            Ok(Block::new(header.clone(), all_txs))
+        };
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+        let validate = |block: &Block| runtime.block_on(block.validate_coinbase_with(5000, &fetch));
+
+        assert!(validate(&block(5150)?)?);
+        assert!(validate(&block(4000)?)?);
+        assert!(!validate(&block(5151)?)?);
+
+        // the coinbase must come first
+        let mut swapped = block(5150)?;
+        swapped.txs.swap(0, 1);
+        assert!(!validate(&swapped)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn chain_linkage() -> Result<()> {
+        let headers = first_mainnet_headers()?;
+
+        assert!(headers[1].connects_to(&headers[0])?);
+        assert!(headers[2].connects_to(&headers[1])?);
+        assert!(!headers[2].connects_to(&headers[0])?);
+        assert!(validate_chain(&headers).is_ok());
+
+        let skipping = [headers[0].clone(), headers[2].clone()];
+        assert!(validate_chain(&skipping).is_err());
+
+        let mut bad_pow = headers.clone();
+        bad_pow[2].nonce += 1;
+        assert!(validate_chain(&bad_pow).is_err());
+
+        Ok(())
+    }
+}
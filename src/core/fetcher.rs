@@ -1,13 +1,14 @@
-use std::io::Cursor;
+use std::future::Future;
+use std::time::{Duration, Instant};
 
-use byteorder::{LittleEndian, ReadBytesExt};
-use bytes::{Buf, BytesMut};
+use bytes::BytesMut;
 use dashmap::DashMap;
 use hyper::body::HttpBody;
 use hyper::client::connect::HttpConnector;
 use hyper::{Client, Uri};
 use lazy_static::lazy_static;
 
+use crate::core::output::Output;
 use crate::core::tx::Tx;
 use crate::utils::default;
 use crate::{Error, Result};
@@ -16,10 +17,22 @@ lazy_static! {
     pub static ref TX_FETCHER: TxFetcher = TxFetcher::new();
 }
 
+/// Environment variable overriding the server transactions are fetched from,
+/// for both networks (e.g. a local node's explorer or a mock in tests)
+pub const FETCHER_URL_VAR: &str = "OXICOIN_FETCHER_URL";
+
 #[derive(Debug)]
 pub struct TxFetcher {
-    cache: DashMap<String, Tx>,
+    cache: DashMap<String, CachedTx>,
     client: Client<HttpConnector>,
+    ttl: Option<Duration>,
+    base_url: Option<String>,
+}
+
+#[derive(Debug)]
+struct CachedTx {
+    tx: Tx,
+    fetched_at: Instant,
 }
 
 impl TxFetcher {
@@ -27,11 +40,24 @@ impl TxFetcher {
         Self {
             cache: default(),
             client: default(),
+            ttl: None,
+            base_url: std::env::var(FETCHER_URL_VAR).ok(),
+        }
+    }
+
+    /// Build a fetcher whose cached transactions are fetched again once
+    /// they're older than `ttl`
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            ttl: Some(ttl),
+            ..Self::new()
         }
     }
 
-    const fn get_url(testnet: bool) -> &'static str {
-        if testnet {
+    fn get_url(&self, testnet: bool) -> &str {
+        if let Some(base_url) = &self.base_url {
+            base_url
+        } else if testnet {
             "http://testnet.programmingbitcoin.com"
         } else {
             "http://mainnet.programmingbitcoin.com"
@@ -39,36 +65,197 @@ impl TxFetcher {
     }
 
     pub async fn fetch(&self, tx_id: &str, testnet: bool, fresh: bool) -> Result<Tx> {
-        if fresh || !self.cache.contains_key(tx_id) {
-            let url = format!("{}/tx/{}.hex", Self::get_url(testnet), hex::encode(tx_id));
-            let uri: Uri = url.parse().unwrap();
+        self.fetch_with(tx_id, testnet, fresh, || self.download(tx_id, testnet))
+            .await
+    }
+
+    /// Get `tx_id` from the cache, unless it's missing, expired or `fresh` is
+    /// requested, in which case it's obtained with `download`
+    async fn fetch_with<F, Fut>(
+        &self,
+        tx_id: &str,
+        testnet: bool,
+        fresh: bool,
+        download: F,
+    ) -> Result<Tx>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Tx>>,
+    {
+        if fresh || !self.is_cached(tx_id) {
+            let tx = download().await?;
+            let fetched_at = Instant::now();
+            self.cache
+                .insert(tx_id.to_string(), CachedTx { tx, fetched_at });
+        }
+
+        let mut cached = self.cache.get_mut(tx_id).unwrap();
+        cached.tx.testnet = testnet;
+        Ok(cached.tx.clone())
+    }
+
+    /// Check whether `tx_id` is cached and not expired
+    fn is_cached(&self, tx_id: &str) -> bool {
+        match (self.cache.get(tx_id), self.ttl) {
+            (Some(cached), Some(ttl)) => cached.fetched_at.elapsed() < ttl,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
 
-            let mut response = self.client.get(uri).await?;
-            let mut bytes = BytesMut::with_capacity(response.size_hint().lower() as usize);
+    async fn download(&self, tx_id: &str, testnet: bool) -> Result<Tx> {
+        let url = format!("{}/tx/{}.hex", self.get_url(testnet), tx_id);
+        let body = self.get(&url).await?;
+        let bytes = hex::decode(String::from_utf8_lossy(&body).trim())?;
+        let tx = Tx::deserialize(bytes.as_slice(), testnet)?;
 
-            while let Some(chunk) = response.data().await {
-                bytes.extend_from_slice(&chunk?);
-            }
+        if tx.id()? != tx_id {
+            return Err(Error::FetchedInvalidTransaction);
+        }
 
-            let tx = if bytes[4] == 0x0 {
-                let chain = bytes[..4].chain(&bytes[6..]);
-                let mut tx = Tx::deserialize(chain, testnet)?;
-                let mut last_four = Cursor::new(&bytes[(bytes.len() - 4)..]);
-                tx.locktime = last_four.read_u64::<LittleEndian>()?;
+        Ok(tx)
+    }
 
-                tx
-            } else {
-                Tx::deserialize(bytes, testnet)?
-            };
+    /// Get the body of `url`, failing if the response isn't successful
+    async fn get(&self, url: &str) -> Result<BytesMut> {
+        let uri: Uri = url.parse().unwrap();
 
-            if tx.id()? != tx_id {
-                return Err(Error::FetchedInvalidTransaction);
-            }
+        let mut response = self.client.get(uri).await?;
+        if !response.status().is_success() {
+            return Err(Error::FetchHttpStatus(response.status().as_u16()));
+        }
 
-            self.cache.insert(tx_id.to_string(), tx);
+        let mut bytes = BytesMut::with_capacity(response.size_hint().lower() as usize);
+        while let Some(chunk) = response.data().await {
+            bytes.extend_from_slice(&chunk?);
         }
 
-        self.cache.get_mut(tx_id).unwrap().testnet = testnet;
-        return Ok(self.cache.get(tx_id).unwrap().value().clone());
+        Ok(bytes)
+    }
+
+    /// Fetch the output at `index` of the transaction `tx_id`, failing if the
+    /// transaction doesn't have such output
+    pub async fn fetch_output(&self, tx_id: &str, index: u32, testnet: bool) -> Result<Output> {
+        let tx = self.fetch(tx_id, testnet, false).await?;
+
+        tx.outputs
+            .get(index as usize)
+            .cloned()
+            .ok_or(Error::OutputIndexOutOfRange(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use anyhow::Result;
+
+    use super::*;
+    use crate::core::script::Script;
+
+    #[test]
+    fn fetch_output_out_of_range() -> Result<()> {
+        let fetcher = TxFetcher::new();
+        let output = Output {
+            amount: 1000,
+            script_pubkey: Script::new(),
+        };
+
+        let tx = Tx {
+            version: 1,
+            inputs: vec![],
+            outputs: vec![output],
+            locktime: 0,
+            testnet: false,
+            cached_hash: default(),
+        };
+
+        // already cached, so nothing is actually fetched
+        let tx_id = tx.id()?;
+        let fetched_at = Instant::now();
+        fetcher
+            .cache
+            .insert(tx_id.clone(), CachedTx { tx, fetched_at });
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+        runtime.block_on(async {
+            let output = fetcher.fetch_output(&tx_id, 0, false).await?;
+            assert_eq!(output.amount, 1000);
+
+            let result = fetcher.fetch_output(&tx_id, 1, false).await;
+            assert!(matches!(result, Err(Error::OutputIndexOutOfRange(1))));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn non_successful_status() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/tx/missing.hex", listener.local_addr()?);
+
+        let server = thread::spawn(move || -> std::io::Result<()> {
+            let (mut stream, _) = listener.accept()?;
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request)?;
+            stream.write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 9\r\n\r\nnot found")
+        });
+
+        let fetcher = TxFetcher::new();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        let result = runtime.block_on(fetcher.get(&url));
+        assert!(matches!(result, Err(Error::FetchHttpStatus(404))));
+
+        server.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn expired_entries_are_fetched_again() -> Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let tx = Tx {
+            version: 1,
+            inputs: vec![],
+            outputs: vec![],
+            locktime: 0,
+            testnet: false,
+            cached_hash: default(),
+        };
+        let tx_id = tx.id()?;
+
+        let downloads = AtomicUsize::new(0);
+        let download = || async {
+            downloads.fetch_add(1, Ordering::SeqCst);
+            Ok(tx.clone())
+        };
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+        runtime.block_on(async {
+            let fetcher = TxFetcher::with_ttl(Duration::from_millis(50));
+            fetcher.fetch_with(&tx_id, false, false, download).await?;
+            fetcher.fetch_with(&tx_id, true, false, download).await?;
+            assert_eq!(downloads.load(Ordering::SeqCst), 1);
+
+            thread::sleep(Duration::from_millis(60));
+            let fetched = fetcher.fetch_with(&tx_id, false, false, download).await?;
+            assert_eq!(fetched, tx);
+            assert_eq!(downloads.load(Ordering::SeqCst), 2);
+
+            // without a ttl entries never expire
+            let fetcher = TxFetcher::new();
+            fetcher.fetch_with(&tx_id, false, false, download).await?;
+            thread::sleep(Duration::from_millis(60));
+            fetcher.fetch_with(&tx_id, false, false, download).await?;
+            assert_eq!(downloads.load(Ordering::SeqCst), 3);
+
+            Ok(())
+        })
     }
 }
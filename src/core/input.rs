@@ -1,25 +1,28 @@
-use std::io::Read;
+use std::convert::TryInto;
 
 use byteorder::{LittleEndian, ReadBytesExt};
 use bytes::{Buf, Bytes};
 use derivative::Derivative;
 
 use crate::core::tx::Tx;
-use crate::Result;
+use crate::utils::{read_u256_le, write_u256_le};
+use crate::{Error, Result};
 
 use super::fetcher::TX_FETCHER;
+use super::output::Output;
 use super::script::Script;
+use super::witness::Witness;
 
-#[derive(Derivative, Clone)]
+#[derive(Derivative, Clone, PartialEq, Eq)]
 #[derivative(Debug)]
 pub struct Input {
     #[derivative(Debug(format_with = "crate::format::bytes::fmt"))]
     pub(crate) prev_tx: Bytes, // size: 32 bytes
     pub(crate) prev_idx: u32,
-    #[derivative(Debug = "ignore")]
     pub(crate) script_sig: Script, // size: variable
-    #[derivative(Debug = "ignore")]
     pub(crate) sequence: u32,
+    #[derivative(Debug = "ignore")]
+    pub(crate) witness: Witness, // only for segwit transactions
 }
 
 impl Input {
@@ -29,15 +32,22 @@ impl Input {
     where
         B: AsRef<[u8]>,
     {
-        let prev_tx = Bytes::copy_from_slice(prev_tx.as_ref());
+        let prev_tx = prev_tx.as_ref();
+        if prev_tx.len() != 32 {
+            return Err(Error::InvalidPrevTxLength(prev_tx.len()));
+        }
+
+        let prev_tx = Bytes::copy_from_slice(prev_tx);
         let script_sig = Script::new();
         let sequence = Self::DEFAULT_SEQUENCE;
+        let witness = Witness::new();
 
         Ok(Self {
             prev_tx,
             prev_idx,
             script_sig,
             sequence,
+            witness,
         })
     }
 
@@ -46,21 +56,49 @@ impl Input {
         TX_FETCHER.fetch(&tx_id, testnet, false).await
     }
 
-    pub fn value(&self, tx: &Tx) -> u64 {
-        tx.outputs[self.prev_idx as usize].amount
+    /// Get the amount of the output spent by this input, `tx` being the
+    /// previous transaction
+    pub fn value(&self, tx: &Tx) -> Result<u64> {
+        Ok(self.prev_output(tx)?.amount)
     }
 
-    pub fn script_pubkey<'a>(&self, tx: &'a Tx) -> &'a Script {
-        &tx.outputs[self.prev_idx as usize].script_pubkey
+    /// Get the script pubkey of the output spent by this input, `tx` being the
+    /// previous transaction
+    pub fn script_pubkey<'a>(&self, tx: &'a Tx) -> Result<&'a Script> {
+        Ok(&self.prev_output(tx)?.script_pubkey)
+    }
+
+    /// Get the legacy signature hash of this input, which must be the one at
+    /// `index` in `tx`, see [`Tx::sig_hash`]
+    pub fn sig_hash(&self, tx: &Tx, index: usize, script_pubkey: &Script) -> Result<[u8; 32]> {
+        if tx.inputs.get(index) != Some(self) {
+            return Err(Error::InvalidTransaction("input not at index"));
+        }
+
+        tx.sig_hash(index, script_pubkey)
+    }
+
+    fn prev_output<'a>(&self, tx: &'a Tx) -> Result<&'a Output> {
+        tx.outputs
+            .get(self.prev_idx as usize)
+            .ok_or(Error::OutputIndexOutOfRange(self.prev_idx))
     }
 
     pub fn serialize(&self) -> Result<Vec<u8>> {
-        let prev_tx_bytes = self.prev_tx.iter().copied().rev();
+        let prev_tx: &[u8; 32] = self
+            .prev_tx
+            .as_ref()
+            .try_into()
+            .map_err(|_| Error::InvalidPrevTxLength(self.prev_tx.len()))?;
+
+        let prev_tx_bytes = write_u256_le(prev_tx);
         let prev_idx_bytes = self.prev_idx.to_le_bytes();
         let script_sig_bytes = self.script_sig.serialize()?.into_iter();
         let sequence_bytes = self.sequence.to_le_bytes();
 
         let result = prev_tx_bytes
+            .iter()
+            .copied()
             .chain(prev_idx_bytes.iter().copied())
             .chain(script_sig_bytes)
             .chain(sequence_bytes.iter().copied())
@@ -72,20 +110,99 @@ impl Input {
     pub fn deserialize(buf: impl Buf) -> Result<Self> {
         let mut reader = buf.reader();
 
-        let mut prev_tx_bytes = [0u8; 32];
-        reader.read_exact(&mut prev_tx_bytes)?;
-        prev_tx_bytes.reverse();
+        let prev_tx_bytes = read_u256_le(&mut reader)?;
         let prev_tx = Bytes::copy_from_slice(&prev_tx_bytes[..]);
 
         let prev_idx = reader.read_u32::<LittleEndian>()?;
-        let script_sig = Script::deserialize(reader.get_mut())?;
+        let (script_sig, _) = Script::deserialize(reader.get_mut())?;
         let sequence = reader.read_u32::<LittleEndian>()?;
+        let witness = Witness::new(); // filled by the transaction, if any
 
         Ok(Self {
             prev_tx,
             prev_idx,
             script_sig,
             sequence,
+            witness,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use hex_literal::hex;
+
+    use super::*;
+    use crate::utils::default;
+
+    #[test]
+    fn debug_format() -> Result<()> {
+        let bytes = hex!(
+            "813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100
+            ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d
+            446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b
+            8138bd94bdd531d2e213bf016b278afeffffff"
+        );
+
+        let input = Input::deserialize(&bytes[..])?;
+        insta::assert_debug_snapshot!(input);
+
+        Ok(())
+    }
+
+    #[test]
+    fn prev_idx_out_of_range() -> Result<()> {
+        let prev_tx = Tx {
+            version: 1,
+            inputs: vec![],
+            outputs: vec![Output {
+                amount: 1000,
+                script_pubkey: Script::new(),
+            }],
+            locktime: 0,
+            testnet: false,
+            cached_hash: default(),
+        };
+
+        let input = Input::new(prev_tx.hash()?, 0)?;
+        assert_eq!(input.value(&prev_tx)?, 1000);
+        assert!(input.script_pubkey(&prev_tx)?.cmds.is_empty());
+
+        let input = Input::new(prev_tx.hash()?, 1)?;
+        assert!(matches!(
+            input.value(&prev_tx),
+            Err(Error::OutputIndexOutOfRange(1))
+        ));
+        assert!(input.script_pubkey(&prev_tx).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn sig_hash() -> Result<()> {
+        let bytes = hex!(
+            "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b
+            483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a98
+            6d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545
+            de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b
+            654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e33216
+            6702cb75f40df79fea1288ac19430600"
+        );
+        let tx = Tx::deserialize(&bytes[..], false)?;
+        let input = &tx.inputs()[0];
+
+        let prev_script = Script::p2pkh(&hex!("a802fc56c704ce87c42d7c92eb75e7896bdc41ae"));
+        assert_eq!(
+            input.sig_hash(&tx, 0, &prev_script)?,
+            tx.sig_hash(0, &prev_script)?
+        );
+
+        assert!(input.sig_hash(&tx, 1, &prev_script).is_err());
+        let other = Input::new([0x00; 32], 0)?;
+        assert!(other.sig_hash(&tx, 0, &prev_script).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+//! Merkle trees over hashes in their internal byte order (i.e. not reversed
+//! as when displayed), as used for the transactions of a block.
+
+use num_integer::Integer;
+
+use crate::utils::hash256;
+
+/// Hash of the concatenation of two nodes
+pub fn merkle_parent(left: &[u8], right: &[u8]) -> Vec<u8> {
+    hash256([left, right].concat())
+}
+
+/// Compute the level above `hashes`, the last hash is paired with itself if
+/// there's an odd number of them
+pub fn merkle_parent_level(hashes: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    hashes
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => merkle_parent(left, right),
+            [last] => merkle_parent(last, last),
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// Compute the root of the tree with the given leaves, `None` if empty
+pub fn merkle_root(hashes: &[Vec<u8>]) -> Option<Vec<u8>> {
+    let mut level = hashes.to_vec();
+    while level.len() > 1 {
+        level = merkle_parent_level(&level);
+    }
+
+    level.pop()
+}
+
+/// Get the path proving the inclusion of the leaf at `index`, from the bottom
+/// up. Each step is a sibling and whether it's on the right side of the node
+/// being proven. Returns `None` if `index` is out of range.
+pub fn merkle_proof(hashes: &[Vec<u8>], mut index: usize) -> Option<Vec<(bool, Vec<u8>)>> {
+    if index >= hashes.len() {
+        return None;
+    }
+
+    let mut proof = Vec::new();
+    let mut level = hashes.to_vec();
+
+    while level.len() > 1 {
+        let step = if index.is_even() {
+            // the last node is its own sibling on odd levels
+            let sibling = level.get(index + 1).unwrap_or(&level[index]);
+            (true, sibling.clone())
+        } else {
+            (false, level[index - 1].clone())
+        };
+
+        proof.push(step);
+        level = merkle_parent_level(&level);
+        index /= 2;
+    }
+
+    Some(proof)
+}
+
+/// Check whether `proof` links `leaf` to `root`
+pub fn verify_merkle_proof(leaf: &[u8], proof: &[(bool, Vec<u8>)], root: &[u8]) -> bool {
+    let computed = proof
+        .iter()
+        .fold(leaf.to_vec(), |node, (sibling_on_right, sibling)| {
+            if *sibling_on_right {
+                merkle_parent(&node, sibling)
+            } else {
+                merkle_parent(sibling, &node)
+            }
+        });
+
+    computed == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::reverse_hex;
+
+    fn block_100000_hashes() -> Vec<Vec<u8>> {
+        [
+            "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+            "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+            "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+            "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+        ]
+        .iter()
+        .map(|txid| hex::decode(reverse_hex(txid).unwrap()).unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn block_merkle_root() {
+        let root = merkle_root(&block_100000_hashes()).unwrap();
+        let expected = "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766";
+        assert_eq!(hex::encode(root), reverse_hex(expected).unwrap());
+
+        assert_eq!(merkle_root(&[]), None);
+    }
+
+    #[test]
+    fn proof_for_every_leaf() {
+        let mut hashes = block_100000_hashes();
+        hashes.push(hash256(b"odd one out"));
+        let root = merkle_root(&hashes).unwrap();
+
+        for (index, leaf) in hashes.iter().enumerate() {
+            let proof = merkle_proof(&hashes, index).unwrap();
+            assert_eq!(proof.len(), 3);
+            assert!(verify_merkle_proof(leaf, &proof, &root));
+            assert!(!verify_merkle_proof(
+                &hashes[(index + 1) % 5],
+                &proof,
+                &root
+            ));
+        }
+
+        assert_eq!(merkle_proof(&hashes, 5), None);
+    }
+}
@@ -1,5 +1,8 @@
+pub mod block;
 pub mod fetcher;
 pub mod input;
+pub mod merkle;
 pub mod output;
 pub mod script;
 pub mod tx;
+pub mod witness;
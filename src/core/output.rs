@@ -5,13 +5,34 @@ use crate::Result;
 
 use super::script::Script;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Output {
     pub(crate) amount: u64,
     pub(crate) script_pubkey: Script,
 }
 
 impl Output {
+    /// Estimated size of the input spending an output (outpoint, a p2pkh script
+    /// sig and sequence), as used by Bitcoin Core for the dust threshold.
+    const SPENDING_INPUT_SIZE: u64 = 32 + 4 + 1 + 107 + 4;
+
+    /// Check whether this output is dust, i.e. its amount is lower than three
+    /// times the cost of spending it at `fee_rate` (in satoshis per byte).
+    pub fn is_dust(&self, fee_rate: u64) -> Result<bool> {
+        let size = self.serialize()?.len() as u64 + Self::SPENDING_INPUT_SIZE;
+        let threshold = size.saturating_mul(fee_rate).saturating_mul(3);
+
+        Ok(self.amount < threshold)
+    }
+
+    pub fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    pub fn script_pubkey(&self) -> &Script {
+        &self.script_pubkey
+    }
+
     pub fn serialize(&self) -> Result<Vec<u8>> {
         let result = self
             .amount
@@ -28,7 +49,7 @@ impl Output {
         let mut reader = buf.reader();
 
         let amount = reader.read_u64::<LittleEndian>()?;
-        let script_pubkey = Script::deserialize(reader.get_mut())?;
+        let (script_pubkey, _) = Script::deserialize(reader.get_mut())?;
 
         Ok(Self {
             amount,
@@ -36,3 +57,31 @@ impl Output {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use hex_literal::hex;
+
+    use super::*;
+
+    #[test]
+    fn p2pkh_dust_threshold() -> Result<()> {
+        let bytes = hex!("1976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac");
+        let (script_pubkey, _) = Script::deserialize(&bytes[..])?;
+
+        let mut output = Output {
+            amount: 546,
+            script_pubkey,
+        };
+
+        assert!(!output.is_dust(1)?);
+        assert!(output.is_dust(2)?);
+
+        output.amount = 545;
+        assert!(output.is_dust(1)?);
+        assert!(!output.is_dust(0)?);
+
+        Ok(())
+    }
+}
@@ -1,20 +1,852 @@
-use bytes::Buf;
+use std::convert::TryFrom;
+use std::fmt::{self, Display, Formatter};
+use std::io::Read;
 
-use crate::Result;
+use byteorder::{LittleEndian, ReadBytesExt};
+use bytes::{Buf, Bytes};
 
-#[derive(Debug, Clone)]
-pub struct Script {}
+use crate::base58;
+use crate::network::Network;
+use crate::varint::VarInt;
+use crate::{Error, Result};
+
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_PUSHDATA4: u8 = 0x4e;
+const OP_16: u8 = 0x60;
+const OP_CODESEPARATOR: u8 = 0xab;
+
+/// Maximum size of a script that can be executed, larger ones always fail
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+
+/// Maximum size of an element pushed by a script
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptCommand {
+    /// Data pushed onto the stack, `OP_0` is an empty element
+    Element(Bytes),
+    OpReturn,
+    OpDup,
+    OpEqual,
+    OpEqualVerify,
+    OpRipemd160,
+    OpSha1,
+    OpSha256,
+    OpHash160,
+    OpHash256,
+    OpCheckSig,
+    OpCheckSigVerify,
+    OpCheckMultiSig,
+    OpCheckMultiSigVerify,
+    /// Any other opcode, kept as is
+    Other(u8),
+}
+
+impl ScriptCommand {
+    fn from_opcode(opcode: u8) -> Self {
+        match opcode {
+            0x6a => Self::OpReturn,
+            0x76 => Self::OpDup,
+            0x87 => Self::OpEqual,
+            0x88 => Self::OpEqualVerify,
+            0xa6 => Self::OpRipemd160,
+            0xa7 => Self::OpSha1,
+            0xa8 => Self::OpSha256,
+            0xa9 => Self::OpHash160,
+            0xaa => Self::OpHash256,
+            0xac => Self::OpCheckSig,
+            0xad => Self::OpCheckSigVerify,
+            0xae => Self::OpCheckMultiSig,
+            0xaf => Self::OpCheckMultiSigVerify,
+            other => Self::Other(other),
+        }
+    }
+
+    /// Get the opcode of this command, `None` for elements
+    pub fn opcode(&self) -> Option<u8> {
+        let opcode = match self {
+            Self::Element(_) => return None,
+            Self::OpReturn => 0x6a,
+            Self::OpDup => 0x76,
+            Self::OpEqual => 0x87,
+            Self::OpEqualVerify => 0x88,
+            Self::OpRipemd160 => 0xa6,
+            Self::OpSha1 => 0xa7,
+            Self::OpSha256 => 0xa8,
+            Self::OpHash160 => 0xa9,
+            Self::OpHash256 => 0xaa,
+            Self::OpCheckSig => 0xac,
+            Self::OpCheckSigVerify => 0xad,
+            Self::OpCheckMultiSig => 0xae,
+            Self::OpCheckMultiSigVerify => 0xaf,
+            Self::Other(opcode) => *opcode,
+        };
+
+        Some(opcode)
+    }
+
+    /// Build the command pushing the (non negative) number `n`, using the
+    /// small number opcodes when possible
+    pub(crate) fn from_number(n: u64) -> Self {
+        match n {
+            0 => Self::Element(Bytes::new()),
+            1..=16 => Self::Other(0x50 + n as u8),
+            _ => {
+                let mut bytes: Vec<_> = n.to_le_bytes().to_vec();
+                while bytes.last() == Some(&0x00) {
+                    bytes.pop();
+                }
+
+                // the most significant bit is the sign
+                if matches!(bytes.last(), Some(b) if b & 0x80 != 0) {
+                    bytes.push(0x00);
+                }
+
+                Self::Element(bytes.into())
+            }
+        }
+    }
+
+    /// Get the non negative number pushed by this command, if any
+    pub(crate) fn as_number(&self) -> Option<u64> {
+        match self {
+            Self::Other(opcode) if (0x51..=OP_16).contains(opcode) => Some((opcode - 0x50) as u64),
+            Self::Element(bytes) if bytes.len() <= 9 => {
+                if matches!(bytes.last(), Some(b) if b & 0x80 != 0) {
+                    return None;
+                }
+
+                let mut le_bytes = [0u8; 9];
+                le_bytes[..bytes.len()].copy_from_slice(bytes);
+                if le_bytes[8] != 0 {
+                    return None;
+                }
+
+                let mut n = [0u8; 8];
+                n.copy_from_slice(&le_bytes[..8]);
+                Some(u64::from_le_bytes(n))
+            }
+
+            _ => None,
+        }
+    }
+
+    fn name(opcode: u8) -> Option<&'static str> {
+        let name = match opcode {
+            0x4f => "OP_1NEGATE",
+            0x61 => "OP_NOP",
+            0x63 => "OP_IF",
+            0x64 => "OP_NOTIF",
+            0x67 => "OP_ELSE",
+            0x68 => "OP_ENDIF",
+            0x69 => "OP_VERIFY",
+            0x6a => "OP_RETURN",
+            0x75 => "OP_DROP",
+            0x76 => "OP_DUP",
+            0x87 => "OP_EQUAL",
+            0x88 => "OP_EQUALVERIFY",
+            0xa6 => "OP_RIPEMD160",
+            0xa7 => "OP_SHA1",
+            0xa8 => "OP_SHA256",
+            0xa9 => "OP_HASH160",
+            0xaa => "OP_HASH256",
+            OP_CODESEPARATOR => "OP_CODESEPARATOR",
+            0xac => "OP_CHECKSIG",
+            0xad => "OP_CHECKSIGVERIFY",
+            0xae => "OP_CHECKMULTISIG",
+            0xaf => "OP_CHECKMULTISIGVERIFY",
+            0xb1 => "OP_CHECKLOCKTIMEVERIFY",
+            0xb2 => "OP_CHECKSEQUENCEVERIFY",
+            _ => return None,
+        };
+
+        Some(name)
+    }
+
+    fn serialize(&self) -> Result<Vec<u8>> {
+        let element = match self {
+            Self::Element(element) => element,
+            cmd => return Ok(vec![cmd.opcode().unwrap()]), // safe
+        };
+
+        let length = element.len();
+        let prefix = match length {
+            0..=75 => vec![length as u8],
+            76..=0xff => vec![OP_PUSHDATA1, length as u8],
+            0x100..=0xffff => std::iter::once(OP_PUSHDATA2)
+                .chain((length as u16).to_le_bytes().iter().copied())
+                .collect(),
+            _ => {
+                let length =
+                    u32::try_from(length).map_err(|_| Error::InvalidScript("element too long"))?;
+
+                std::iter::once(OP_PUSHDATA4)
+                    .chain(length.to_le_bytes().iter().copied())
+                    .collect()
+            }
+        };
+
+        Ok(prefix.into_iter().chain(element.iter().copied()).collect())
+    }
+}
+
+/// Display the command in ASM, elements are shown as hex
+impl Display for ScriptCommand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Element(element) if element.is_empty() => write!(f, "OP_0"),
+            Self::Element(element) => write!(f, "{}", hex::encode(element)),
+            cmd => {
+                let opcode = cmd.opcode().unwrap(); // safe
+                match Self::name(opcode) {
+                    Some(name) => write!(f, "{}", name),
+                    None if (0x51..=OP_16).contains(&opcode) => write!(f, "OP_{}", opcode - 0x50),
+                    None => write!(f, "OP_UNKNOWN({:#04x})", opcode),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Script {
+    pub(crate) cmds: Vec<ScriptCommand>,
+}
+
+impl From<Vec<ScriptCommand>> for Script {
+    fn from(cmds: Vec<ScriptCommand>) -> Self {
+        Self { cmds }
+    }
+}
+
+/// Display the script in ASM, i.e. its commands separated by spaces
+impl Display for Script {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, cmd) in self.cmds.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            write!(f, "{}", cmd)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Script({})", self)
+    }
+}
 
 impl Script {
     pub fn new() -> Self {
-        Self {}
+        Self { cmds: Vec::new() }
+    }
+
+    /// Build a pay-to-pubkey-hash script: `OP_DUP OP_HASH160 <hash> OP_EQUALVERIFY OP_CHECKSIG`
+    pub fn p2pkh(hash: &[u8; 20]) -> Self {
+        Self::from(vec![
+            ScriptCommand::OpDup,
+            ScriptCommand::OpHash160,
+            ScriptCommand::Element(Bytes::copy_from_slice(hash)),
+            ScriptCommand::OpEqualVerify,
+            ScriptCommand::OpCheckSig,
+        ])
+    }
+
+    /// Build a pay-to-script-hash script: `OP_HASH160 <hash> OP_EQUAL`
+    pub fn p2sh(hash: &[u8; 20]) -> Self {
+        Self::from(vec![
+            ScriptCommand::OpHash160,
+            ScriptCommand::Element(Bytes::copy_from_slice(hash)),
+            ScriptCommand::OpEqual,
+        ])
+    }
+
+    /// Build the script pubkey paying to a (base58) p2pkh or p2sh address, of
+    /// any network
+    pub fn for_address<S: AsRef<str>>(address: S) -> Result<Self> {
+        let decoded = base58::decode_checksum(address)?;
+
+        let (prefix, hash) = match decoded.as_slice() {
+            [prefix, hash @ ..] if hash.len() == 20 => (*prefix, hash),
+            _ => return Err(Error::InvalidAddress("expecting 21 bytes")),
+        };
+
+        let hash = <&[u8; 20]>::try_from(hash).unwrap(); // safe
+        let networks = [Network::Mainnet, Network::Testnet];
+
+        if networks.iter().any(|n| n.params().p2pkh_prefix == prefix) {
+            Ok(Self::p2pkh(hash))
+        } else if networks.iter().any(|n| n.params().p2sh_prefix == prefix) {
+            Ok(Self::p2sh(hash))
+        } else {
+            Err(Error::InvalidAddress("unknown prefix"))
+        }
+    }
+
+    /// Get the (base58) address this p2pkh or p2sh script pubkey pays to,
+    /// `None` for any other kind of script
+    pub fn address(&self, network: Network) -> Option<String> {
+        use ScriptCommand::*;
+
+        let params = network.params();
+        let (prefix, hash) = match self.cmds.as_slice() {
+            [OpDup, OpHash160, Element(hash), OpEqualVerify, OpCheckSig] if hash.len() == 20 => {
+                (params.p2pkh_prefix, hash)
+            }
+            [OpHash160, Element(hash), OpEqual] if hash.len() == 20 => (params.p2sh_prefix, hash),
+            _ => return None,
+        };
+
+        let data: Vec<_> = std::iter::once(prefix)
+            .chain(hash.iter().copied())
+            .collect();
+        base58::encode_checksum(data).ok()
+    }
+
+    /// Iterate over the commands of this script
+    pub fn iter(&self) -> impl Iterator<Item = &ScriptCommand> {
+        self.cmds.iter()
+    }
+
+    /// Number of commands in this script
+    pub fn len(&self) -> usize {
+        self.cmds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cmds.is_empty()
+    }
+
+    /// Count the signature operations of this script, `OP_CHECKMULTISIG` and
+    /// `OP_CHECKMULTISIGVERIFY` count as the maximum number of keys (20)
+    pub fn sig_op_count(&self) -> usize {
+        self.count_sig_ops(false)
+    }
+
+    /// Count the signature operations of this script, using the number of
+    /// keys pushed right before `OP_CHECKMULTISIG(VERIFY)` when available (as
+    /// done for p2sh redeem scripts)
+    pub fn accurate_sig_op_count(&self) -> usize {
+        self.count_sig_ops(true)
+    }
+
+    fn count_sig_ops(&self, accurate: bool) -> usize {
+        const MAX_PUBKEYS_PER_MULTISIG: usize = 20;
+
+        let mut count = 0;
+        let mut last: Option<&ScriptCommand> = None;
+
+        for cmd in &self.cmds {
+            match cmd {
+                ScriptCommand::OpCheckSig | ScriptCommand::OpCheckSigVerify => count += 1,
+                ScriptCommand::OpCheckMultiSig | ScriptCommand::OpCheckMultiSigVerify => {
+                    count += match last {
+                        Some(ScriptCommand::Other(opcode))
+                            if accurate && (0x51..=OP_16).contains(opcode) =>
+                        {
+                            (opcode - 0x50) as usize
+                        }
+                        _ => MAX_PUBKEYS_PER_MULTISIG,
+                    }
+                }
+                _ => {}
+            }
+
+            last = Some(cmd);
+        }
+
+        count
+    }
+
+    /// Check whether every command only pushes data (elements or small number
+    /// opcodes, up to `OP_16`), as required for standard script sigs
+    pub fn is_push_only(&self) -> bool {
+        self.cmds.iter().all(|cmd| match cmd {
+            ScriptCommand::Element(_) => true,
+            ScriptCommand::Other(opcode) => *opcode <= OP_16,
+            _ => false,
+        })
+    }
+
+    /// Get the data carried by an `OP_RETURN <data>` script, i.e. the
+    /// concatenation of everything pushed after `OP_RETURN`. Returns `None` if
+    /// the script doesn't start with `OP_RETURN` or isn't push-only after it.
+    pub fn op_return_data(&self) -> Option<Vec<u8>> {
+        match self.cmds.split_first() {
+            Some((ScriptCommand::OpReturn, pushes)) => {
+                pushes
+                    .iter()
+                    .try_fold(Vec::new(), |mut data, cmd| match cmd {
+                        ScriptCommand::Element(element) => {
+                            data.extend_from_slice(element);
+                            Some(data)
+                        }
+                        _ => None,
+                    })
+            }
+
+            _ => None,
+        }
+    }
+
+    /// Check whether this script can never be satisfied, i.e. it starts with
+    /// `OP_RETURN` or is larger than [`MAX_SCRIPT_SIZE`]
+    pub fn is_unspendable(&self) -> bool {
+        if let Some(ScriptCommand::OpReturn) = self.cmds.first() {
+            return true;
+        }
+
+        match self.raw_serialize() {
+            Ok(raw) => raw.len() > MAX_SCRIPT_SIZE,
+            Err(_) => true,
+        }
+    }
+
+    /// Check the size limits a script must satisfy to be executed, i.e. it
+    /// isn't larger than [`MAX_SCRIPT_SIZE`] nor pushes elements larger than
+    /// [`MAX_SCRIPT_ELEMENT_SIZE`]. Parsing doesn't enforce them, since scripts
+    /// that break them can still appear in valid transactions.
+    pub fn check_limits(&self) -> Result<()> {
+        if self.raw_serialize()?.len() > MAX_SCRIPT_SIZE {
+            return Err(Error::InvalidScript("script exceeds the maximum size"));
+        }
+
+        let oversized = self.cmds.iter().any(
+            |cmd| matches!(cmd, ScriptCommand::Element(e) if e.len() > MAX_SCRIPT_ELEMENT_SIZE),
+        );
+        if oversized {
+            return Err(Error::InvalidScript("element exceeds the maximum size"));
+        }
+
+        Ok(())
+    }
+
+    /// Get the script committed to by a legacy signature hash: the commands
+    /// after the last `OP_CODESEPARATOR`, without any push of `signature`
+    /// (what Bitcoin Core calls `FindAndDelete`). Without an interpreter, the
+    /// last separator in the script is taken as the last executed one.
+    pub fn sig_hash_subscript(&self, signature: &[u8]) -> Script {
+        let cmds = self
+            .after_code_separator()
+            .iter()
+            .filter(|cmd| !matches!(cmd, ScriptCommand::Element(e) if e.as_ref() == signature))
+            .cloned()
+            .collect();
+
+        Script { cmds }
+    }
+
+    /// Commands after the last `OP_CODESEPARATOR`, all of them if there's none
+    pub(crate) fn after_code_separator(&self) -> &[ScriptCommand] {
+        let start = self
+            .cmds
+            .iter()
+            .rposition(|cmd| matches!(cmd, ScriptCommand::Other(OP_CODESEPARATOR)))
+            .map_or(0, |position| position + 1);
+
+        &self.cmds[start..]
+    }
+
+    /// Serialize the commands, without the length prefix
+    fn raw_serialize(&self) -> Result<Vec<u8>> {
+        let result = self
+            .cmds
+            .iter()
+            .map(|cmd| cmd.serialize())
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .collect();
+
+        Ok(result)
     }
 
     pub fn serialize(&self) -> Result<Vec<u8>> {
-        todo!()
+        let raw = self.raw_serialize()?;
+        let length = VarInt::try_from(raw.len())?;
+
+        Ok(length.serialize().into_iter().chain(raw).collect())
+    }
+
+    /// Deserialize a script, returning it along with the number of bytes
+    /// consumed (length prefix included)
+    pub fn deserialize(buf: impl Buf) -> Result<(Self, usize)> {
+        let mut reader = buf.reader();
+
+        let length = VarInt::deserialize(reader.get_mut())?;
+        let prefix_length = length.serialize().len();
+        let length = length.as_u64();
+
+        let mut cmds = Vec::new();
+        let mut consumed = 0;
+
+        while consumed < length {
+            let current = reader.read_u8()?;
+            consumed += 1;
+
+            let element_length = match current {
+                0x00..=0x4b => current as u64,
+                OP_PUSHDATA1 => {
+                    consumed += 1;
+                    reader.read_u8()? as u64
+                }
+                OP_PUSHDATA2 => {
+                    consumed += 2;
+                    reader.read_u16::<LittleEndian>()? as u64
+                }
+                OP_PUSHDATA4 => {
+                    consumed += 4;
+                    reader.read_u32::<LittleEndian>()? as u64
+                }
+                opcode => {
+                    cmds.push(ScriptCommand::from_opcode(opcode));
+                    continue;
+                }
+            };
+
+            if consumed + element_length > length {
+                return Err(Error::InvalidScript("element exceeds script length"));
+            }
+
+            // the length is untrusted, don't allocate more than what's left
+            if element_length > reader.get_ref().remaining() as u64 {
+                return Err(Error::InvalidScript("element exceeds the data"));
+            }
+
+            let mut element = vec![0u8; element_length as usize];
+            reader.read_exact(&mut element)?;
+            consumed += element_length;
+
+            cmds.push(ScriptCommand::Element(element.into()));
+        }
+
+        if consumed != length {
+            return Err(Error::InvalidScript("parsing script failed"));
+        }
+
+        Ok((Self { cmds }, prefix_length + length as usize))
+    }
+}
+
+/// Get the script pubkey paying to a (base58) p2pkh or p2sh address, see
+/// [`Script::for_address`]
+pub fn script_pubkey_from_address(addr: &str) -> Result<Script> {
+    Script::for_address(addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use hex_literal::hex;
+
+    use super::*;
+
+    #[test]
+    fn p2pkh_script() -> Result<()> {
+        let bytes = hex!("1976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac");
+        let (script, _) = Script::deserialize(&bytes[..])?;
+
+        let hash = hex!("bc3b654dca7e56b04dca18f2566cdaf02e8d9ada");
+        let expected = vec![
+            ScriptCommand::OpDup,
+            ScriptCommand::OpHash160,
+            ScriptCommand::Element(Bytes::copy_from_slice(&hash)),
+            ScriptCommand::OpEqualVerify,
+            ScriptCommand::OpCheckSig,
+        ];
+
+        assert_eq!(script.cmds, expected);
+        assert_eq!(script.serialize()?, bytes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn iterate_commands() -> Result<()> {
+        let bytes = hex!("1976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac");
+        let (script, _) = Script::deserialize(&bytes[..])?;
+
+        let opcodes: Vec<_> = script.iter().map(ScriptCommand::opcode).collect();
+        let expected = vec![Some(0x76), Some(0xa9), None, Some(0x88), Some(0xac)];
+
+        assert_eq!(opcodes, expected);
+        assert_eq!(script.len(), 5);
+        assert!(!script.is_empty());
+        assert!(Script::new().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn sig_op_count() -> Result<()> {
+        let bytes = hex!("1976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac");
+        let (p2pkh, _) = Script::deserialize(&bytes[..])?;
+        assert_eq!(p2pkh.sig_op_count(), 1);
+        assert_eq!(p2pkh.accurate_sig_op_count(), 1);
+
+        // OP_2 <pubkey> <pubkey> <pubkey> OP_3 OP_CHECKMULTISIG
+        let pubkey = ScriptCommand::Element(Bytes::from(vec![0x02; 33]));
+        let multisig = Script::from(vec![
+            ScriptCommand::Other(0x52),
+            pubkey.clone(),
+            pubkey.clone(),
+            pubkey,
+            ScriptCommand::Other(0x53),
+            ScriptCommand::OpCheckMultiSig,
+        ]);
+        assert_eq!(multisig.sig_op_count(), 20);
+        assert_eq!(multisig.accurate_sig_op_count(), 3);
+
+        assert_eq!(Script::new().sig_op_count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn pushdata_elements() -> Result<()> {
+        for &length in &[0usize, 75, 76, 255, 256, 520] {
+            let element = Bytes::from(vec![0xab; length]);
+            let script = Script::from(vec![ScriptCommand::Element(element.clone())]);
+
+            let serialized = script.serialize()?;
+            let (deserialized, _) = Script::deserialize(serialized.as_slice())?;
+            assert_eq!(deserialized.cmds, vec![ScriptCommand::Element(element)]);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn size_limits() -> Result<()> {
+        let element = ScriptCommand::Element(Bytes::from(vec![0xab; MAX_SCRIPT_ELEMENT_SIZE]));
+        let script = Script::from(vec![element]);
+        assert!(script.check_limits().is_ok());
+
+        // oversized scripts still parse, they just can't be executed
+        let element = ScriptCommand::Element(Bytes::from(vec![0xab; MAX_SCRIPT_ELEMENT_SIZE + 1]));
+        let serialized = Script::from(vec![element]).serialize()?;
+        let (script, _) = Script::deserialize(serialized.as_slice())?;
+        let result = script.check_limits();
+        assert!(matches!(result, Err(Error::InvalidScript(_))));
+
+        // 10000 bytes of OP_NOP are fine, one more isn't
+        let nops = |count| Script::from(vec![ScriptCommand::Other(0x61); count]);
+        assert!(nops(MAX_SCRIPT_SIZE).check_limits().is_ok());
+
+        let serialized = nops(MAX_SCRIPT_SIZE + 1).serialize()?;
+        let (script, _) = Script::deserialize(serialized.as_slice())?;
+        assert!(script.is_unspendable());
+        let result = script.check_limits();
+        assert!(matches!(result, Err(Error::InvalidScript(_))));
+
+        // an element claiming more bytes than there are fails without
+        // allocating them
+        let bytes = hex!("ffffffffffffffff004effffffff00");
+        assert!(Script::deserialize(&bytes[..]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn push_only() -> Result<()> {
+        // <sig> <pubkey>
+        let script_sig = Script::from(vec![
+            ScriptCommand::Element(Bytes::from(vec![0x30; 71])),
+            ScriptCommand::Element(Bytes::from(vec![0x02; 33])),
+        ]);
+        assert!(script_sig.is_push_only());
+
+        // OP_0 <sig> OP_1 OP_16
+        let bytes = hex!("06000230015160");
+        assert!(Script::deserialize(&bytes[..])?.0.is_push_only());
+
+        let bytes = hex!("1976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac");
+        assert!(!Script::deserialize(&bytes[..])?.0.is_push_only());
+
+        Ok(())
+    }
+
+    #[test]
+    fn op_return_data() -> Result<()> {
+        // OP_RETURN "hello world"
+        let bytes = hex!("0d6a0b68656c6c6f20776f726c64");
+        let (script, _) = Script::deserialize(&bytes[..])?;
+
+        assert_eq!(script.cmds[0], ScriptCommand::OpReturn);
+        assert_eq!(script.op_return_data(), Some(b"hello world".to_vec()));
+        assert_eq!(script.serialize()?, bytes);
+
+        let bytes = hex!("1976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac");
+        assert_eq!(Script::deserialize(&bytes[..])?.0.op_return_data(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn single_round_hash_opcodes() -> Result<()> {
+        // OP_SHA256 OP_RIPEMD160 OP_SHA1
+        let bytes = hex!("03a8a6a7");
+        let (script, _) = Script::deserialize(&bytes[..])?;
+
+        let expected = vec![
+            ScriptCommand::OpSha256,
+            ScriptCommand::OpRipemd160,
+            ScriptCommand::OpSha1,
+        ];
+
+        assert_eq!(script.cmds, expected);
+        assert_eq!(script.serialize()?, bytes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn consumed_bytes() -> Result<()> {
+        let scripts = vec![
+            Script::new(),
+            Script::from(vec![ScriptCommand::OpReturn]),
+            Script::from(vec![ScriptCommand::Element(Bytes::from(vec![0x01; 300]))]),
+            Script::from(vec![ScriptCommand::Element(Bytes::from(vec![0x01; 80])); 4]),
+        ];
+
+        for script in scripts {
+            let mut serialized = script.serialize()?;
+            let length = serialized.len();
+
+            // trailing bytes belong to whatever comes next
+            serialized.extend_from_slice(&[0xff; 8]);
+            let (deserialized, consumed) = Script::deserialize(serialized.as_slice())?;
+
+            assert_eq!(consumed, length);
+            assert_eq!(deserialized.cmds, script.cmds);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn script_for_address() -> Result<()> {
+        let script = Script::for_address("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy")?;
+        let hash = hex!("b472a266d0bd89c13706a4132ccfb16f7c3b9fcb");
+        assert_eq!(script.cmds, Script::p2sh(&hash).cmds);
+        assert_eq!(
+            script.serialize()?,
+            hex!("17a914b472a266d0bd89c13706a4132ccfb16f7c3b9fcb87")
+        );
+
+        assert_eq!(
+            script.address(Network::Mainnet).as_deref(),
+            Some("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy")
+        );
+
+        // corrupted checksum
+        assert!(Script::for_address("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLz").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn script_pubkey_for_addresses() -> Result<()> {
+        let script = script_pubkey_from_address("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH")?;
+        assert_eq!(
+            script.serialize()?,
+            hex!("1976a914751e76e8199196d454941c45d1b3a323f1433bd688ac")
+        );
+
+        let script = script_pubkey_from_address("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy")?;
+        assert_eq!(
+            script.serialize()?,
+            hex!("17a914b472a266d0bd89c13706a4132ccfb16f7c3b9fcb87")
+        );
+
+        // a WIF decodes fine but isn't an address
+        let wif = "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn";
+        assert!(script_pubkey_from_address(wif).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn unspendable_scripts() -> Result<()> {
+        let bytes = hex!("1976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac");
+        let (p2pkh, _) = Script::deserialize(&bytes[..])?;
+        assert!(!p2pkh.is_unspendable());
+        assert!(!Script::new().is_unspendable());
+
+        let op_return = Script::from(vec![
+            ScriptCommand::OpReturn,
+            ScriptCommand::Element(Bytes::from_static(b"oxicoin")),
+        ]);
+        assert!(op_return.is_unspendable());
+
+        // 2 pushes of 5000 bytes, plus their OP_PUSHDATA2 prefixes
+        let element = ScriptCommand::Element(Bytes::from(vec![0x01; 5000]));
+        let oversized = Script::from(vec![element.clone(), element]);
+        assert!(oversized.is_unspendable());
+
+        Ok(())
+    }
+
+    #[test]
+    fn code_separator_subscript() -> Result<()> {
+        let signature = Bytes::from_static(&[0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01]);
+        let pubkey = Bytes::from(vec![0x02; 33]);
+
+        // <sig> OP_DROP OP_CODESEPARATOR <sig> OP_DROP <pubkey> OP_CHECKSIG
+        let script = Script::from(vec![
+            ScriptCommand::Element(signature.clone()),
+            ScriptCommand::Other(0x75),
+            ScriptCommand::Other(OP_CODESEPARATOR),
+            ScriptCommand::Element(signature.clone()),
+            ScriptCommand::Other(0x75),
+            ScriptCommand::Element(pubkey.clone()),
+            ScriptCommand::OpCheckSig,
+        ]);
+        assert!(script.to_string().contains("OP_CODESEPARATOR"));
+
+        let subscript = script.sig_hash_subscript(&signature);
+        let expected = Script::from(vec![
+            ScriptCommand::Other(0x75),
+            ScriptCommand::Element(pubkey),
+            ScriptCommand::OpCheckSig,
+        ]);
+        assert_eq!(subscript, expected);
+
+        // nothing to strip from a plain p2pkh script
+        let bytes = hex!("1976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac");
+        let (p2pkh, _) = Script::deserialize(&bytes[..])?;
+        assert_eq!(p2pkh.sig_hash_subscript(&signature), p2pkh);
+
+        Ok(())
+    }
+
+    #[test]
+    fn asm_format() -> Result<()> {
+        let bytes = hex!("1976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac");
+        let (script, _) = Script::deserialize(&bytes[..])?;
+        assert_eq!(
+            script.to_string(),
+            "OP_DUP OP_HASH160 bc3b654dca7e56b04dca18f2566cdaf02e8d9ada OP_EQUALVERIFY OP_CHECKSIG"
+        );
+
+        // OP_0 OP_1 OP_16 OP_NOP10
+        let bytes = hex!("04005160b9");
+        let (script, _) = Script::deserialize(&bytes[..])?;
+        assert_eq!(script.to_string(), "OP_0 OP_1 OP_16 OP_UNKNOWN(0xb9)");
+        assert_eq!(format!("{:?}", Script::new()), "Script()");
+
+        Ok(())
     }
 
-    pub fn deserialize(buf: impl Buf) -> Result<Self> {
-        todo!()
+    #[test]
+    fn element_exceeds_script() {
+        // claims a 2 bytes script, but pushes 3 bytes
+        let bytes = hex!("0203aabbcc");
+        assert!(Script::deserialize(&bytes[..]).is_err());
     }
 }
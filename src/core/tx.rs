@@ -1,94 +1,611 @@
+use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::future::Future;
+use std::io::{self, Read, Write};
 
-use byteorder::{LittleEndian, ReadBytesExt};
-use bytes::Buf;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use bytes::{Buf, Bytes};
+use derivative::Derivative;
+use once_cell::sync::OnceCell;
+use sha2::{Digest, Sha256};
 
-use crate::utils::hash256;
+use crate::network::Network;
+use crate::utils::{default, hash256, reverse_bytes};
 use crate::varint::VarInt;
-use crate::Result;
+use crate::{Error, Result};
 
+use super::fetcher::TX_FETCHER;
 use super::input::Input;
+use super::merkle::merkle_root;
 use super::output::Output;
+use super::script::{Script, ScriptCommand};
+use super::witness::Witness;
 
-#[derive(Debug, Clone)]
+/// Relative lock of an input (BIP68)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelativeLockTime {
+    /// Number of blocks since the spent output was confirmed
+    Blocks(u16),
+    /// Number of seconds since the spent output was confirmed, always a
+    /// multiple of 512
+    Time(u32),
+}
+
+impl RelativeLockTime {
+    const DISABLE_FLAG: u32 = 1 << 31;
+    const TYPE_FLAG: u32 = 1 << 22;
+    const VALUE_MASK: u32 = 0x0000ffff;
+    const GRANULARITY: u32 = 9;
+
+    /// Decode the relative lock of an input `sequence`, if it's enabled
+    pub fn from_sequence(sequence: u32) -> Option<Self> {
+        if sequence & Self::DISABLE_FLAG != 0 {
+            return None;
+        }
+
+        let value = sequence & Self::VALUE_MASK;
+        if sequence & Self::TYPE_FLAG != 0 {
+            Some(Self::Time(value << Self::GRANULARITY))
+        } else {
+            Some(Self::Blocks(value as u16))
+        }
+    }
+}
+
+/// Transactions are compared by their consensus fields only, i.e. the
+/// `testnet` flag and caches are ignored
+#[derive(Derivative, Clone)]
+#[derivative(Debug, PartialEq, Eq)]
 pub struct Tx {
     pub(crate) version: u32,
     pub(crate) inputs: Vec<Input>,
     pub(crate) outputs: Vec<Output>,
-    pub(crate) locktime: u64,
+    pub(crate) locktime: u32,
+    #[derivative(PartialEq = "ignore")]
     pub(crate) testnet: bool,
+    #[derivative(Debug = "ignore", PartialEq = "ignore")]
+    pub(crate) cached_hash: OnceCell<Vec<u8>>, // must be reset on any mutation
 }
 
 impl Tx {
+    /// Size of a p2pkh script sig: a DER signature with its sighash byte (at
+    /// most 72 bytes) and a compressed public key, plus their push opcodes
+    const P2PKH_SCRIPT_SIG_SIZE: usize = 1 + 72 + 1 + 33;
+
+    /// Sighash type signing all the inputs and outputs
+    const SIGHASH_ALL: u32 = 1;
+
+    /// Header of the witness commitment output script (BIP141)
+    const WITNESS_COMMITMENT_HEADER: [u8; 4] = [0xaa, 0x21, 0xa9, 0xed];
+
+    /// Build a coinbase transaction for the block at `height` (BIP34), with a
+    /// witness commitment output appended if given.
+    pub fn coinbase(
+        height: u64,
+        mut outputs: Vec<Output>,
+        witness_commitment: Option<[u8; 32]>,
+    ) -> Result<Self> {
+        if outputs.is_empty() && witness_commitment.is_none() {
+            return Err(Error::InvalidTransaction("coinbase without outputs"));
+        }
+
+        let mut input = Input::new([0x00; 32], 0xffffffff)?;
+        let mut cmds = vec![ScriptCommand::from_number(height)];
+        if height <= 16 {
+            // script sigs of coinbase transactions must be at least 2 bytes
+            cmds.push(ScriptCommand::Element(Bytes::new()));
+        }
+        input.script_sig = Script::from(cmds);
+
+        if let Some(commitment) = witness_commitment {
+            let data: Vec<_> = Self::WITNESS_COMMITMENT_HEADER
+                .iter()
+                .chain(commitment.iter())
+                .copied()
+                .collect();
+
+            outputs.push(Output {
+                amount: 0,
+                script_pubkey: Script::from(vec![
+                    ScriptCommand::OpReturn,
+                    ScriptCommand::Element(data.into()),
+                ]),
+            });
+
+            // witness reserved value
+            input.witness = Witness::from(vec![Bytes::from(vec![0x00; 32])]);
+        }
+
+        Ok(Self {
+            version: 1,
+            inputs: vec![input],
+            outputs,
+            locktime: 0,
+            testnet: false,
+            cached_hash: default(),
+        })
+    }
+
+    /// Check whether this is a coinbase transaction, i.e. it has a single
+    /// input not spending any previous output
+    pub fn is_coinbase(&self) -> bool {
+        match self.inputs.as_slice() {
+            [input] => input.prev_tx.iter().all(|b| *b == 0) && input.prev_idx == 0xffffffff,
+            _ => false,
+        }
+    }
+
+    /// Compute the witness commitment of a block with the given transactions,
+    /// the first one being its coinbase (BIP141). That's the hash of the merkle
+    /// root of the wtxids, where the coinbase's one is all zeroes, followed by
+    /// the witness reserved value (also zeroes, as used by [`Tx::coinbase`]).
+    pub fn witness_commitment(txs: &[Tx]) -> Result<[u8; 32]> {
+        if txs.is_empty() {
+            return Err(Error::InvalidTransaction("block without coinbase"));
+        }
+
+        let mut wtxids = vec![vec![0x00; 32]];
+        for tx in &txs[1..] {
+            wtxids.push(hash256(&tx.serialize_with_witness()?));
+        }
+
+        let witness_root = merkle_root(&wtxids).unwrap(); // safe, never empty
+        let commitment = hash256([witness_root, vec![0x00; 32]].concat());
+        Ok(<[u8; 32]>::try_from(commitment.as_slice()).unwrap()) // safe, sha256 output
+    }
+
+    /// Get the block height pushed by the script sig of a coinbase transaction
+    /// (BIP34)
+    pub fn coinbase_height(&self) -> Option<u64> {
+        if !self.is_coinbase() {
+            return None;
+        }
+
+        self.inputs[0].script_sig.cmds.first()?.as_number()
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Get the relative lock of the input at `index`, only version 2 (or
+    /// higher) transactions have them (BIP68)
+    pub fn relative_locktime(&self, index: usize) -> Option<RelativeLockTime> {
+        if self.version < 2 {
+            return None;
+        }
+
+        RelativeLockTime::from_sequence(self.inputs.get(index)?.sequence)
+    }
+
+    pub fn inputs(&self) -> &[Input] {
+        &self.inputs
+    }
+
+    /// Get the outputs of this transaction
+    ///
+    /// ```
+    /// use hex_literal::hex;
+    /// use oxicoin::core::tx::Tx;
+    ///
+    /// let bytes = hex!(
+    ///     "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b
+    ///     483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a98
+    ///     6d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545
+    ///     de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b
+    ///     654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e33216
+    ///     6702cb75f40df79fea1288ac19430600"
+    /// );
+    ///
+    /// let tx = Tx::deserialize(&bytes[..], false)?;
+    /// let total: u64 = tx.outputs().iter().map(|output| output.amount()).sum();
+    /// assert_eq!(total, 32454049 + 10011545);
+    /// # Ok::<(), oxicoin::Error>(())
+    /// ```
+    pub fn outputs(&self) -> &[Output] {
+        &self.outputs
+    }
+
+    pub fn locktime(&self) -> u32 {
+        self.locktime
+    }
+
     pub fn id(&self) -> Result<String> {
         Ok(hex::encode(self.hash()?))
     }
 
+    /// Get the txid of this transaction, in the byte order shown by explorers
+    /// and Bitcoin Core. It's computed over the serialization without witness
+    /// data, so signing a segwit transaction doesn't change it, unlike its
+    /// wtxid which also commits to the witnesses. Same as [`Tx::id`].
+    pub fn txid(&self) -> Result<String> {
+        self.id()
+    }
+
+    /// Get the wtxid of this transaction, computed over its serialization with
+    /// witnesses, so it's the same as the txid for non segwit transactions
+    pub fn wtxid(&self) -> Result<String> {
+        let serialized = self.serialize_with_witness()?;
+        Ok(hex::encode(reverse_bytes(&hash256(&serialized))))
+    }
+
+    /// Get the hash of this transaction, it's only computed the first time
     pub fn hash(&self) -> Result<Vec<u8>> {
-        let serialized = self.serialize()?;
-        let mut digest = hash256(&serialized);
-        digest.reverse();
-        Ok(digest)
+        let hash = self.cached_hash.get_or_try_init(|| -> Result<_> {
+            let serialized = self.serialize()?;
+            Ok(reverse_bytes(&hash256(&serialized)))
+        })?;
+
+        Ok(hash.clone())
     }
 
-    pub async fn fee(&self, testnet: bool) -> Result<u64> {
-        let mut input_sum = 0;
+    /// Sum of the amounts of all the outputs
+    pub fn total_output_value(&self) -> Result<u64> {
+        self.outputs
+            .iter()
+            .try_fold(0u64, |sum, output| sum.checked_add(output.amount))
+            .ok_or(Error::AmountOverflow)
+    }
+
+    /// Get the addresses paid by each output, `None` for non standard script
+    /// pubkeys
+    pub fn output_addresses(&self, network: Network) -> Vec<Option<String>> {
+        self.outputs
+            .iter()
+            .map(|output| output.script_pubkey.address(network))
+            .collect()
+    }
+
+    /// Sum of the amounts spent by all the inputs, fetching the previous
+    /// transactions
+    pub async fn total_input_value(&self, testnet: bool) -> Result<u64> {
+        self.total_input_value_with(move |tx_id| async move {
+            TX_FETCHER.fetch(&tx_id, testnet, false).await
+        })
+        .await
+    }
+
+    /// Sum of the amounts spent by all the inputs, each distinct previous
+    /// transaction is fetched only once and all of them concurrently
+    pub(crate) async fn total_input_value_with<F, Fut>(&self, fetch: F) -> Result<u64>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<Tx>> + Send + 'static,
+    {
+        let mut tx_ids: Vec<_> = self
+            .inputs
+            .iter()
+            .map(|input| hex::encode(&input.prev_tx))
+            .collect();
+        tx_ids.sort_unstable();
+        tx_ids.dedup();
+
+        let handles: Vec<_> = tx_ids
+            .into_iter()
+            .map(|tx_id| (tx_id.clone(), tokio::spawn(fetch(tx_id))))
+            .collect();
+
+        let mut prev_txs = HashMap::with_capacity(handles.len());
+        for (tx_id, handle) in handles {
+            let prev_tx = handle.await.map_err(Error::custom)??;
+            prev_txs.insert(tx_id, prev_tx);
+        }
+
+        let mut sum = 0u64;
         for input in &self.inputs {
-            let prev_tx = input.fetch_tx(testnet).await?;
-            input_sum += input.value(&prev_tx);
+            let prev_tx = &prev_txs[&hex::encode(&input.prev_tx)];
+            sum = sum
+                .checked_add(input.value(prev_tx)?)
+                .ok_or(Error::AmountOverflow)?;
         }
 
-        let output_sum: u64 = self.outputs.iter().map(|output| output.amount).sum();
-        Ok(input_sum - output_sum)
+        Ok(sum)
     }
 
-    pub fn serialize(&self) -> Result<Vec<u8>> {
-        let version_bytes = self.version.to_le_bytes();
+    pub async fn fee(&self, testnet: bool) -> Result<u64> {
+        self.fee_with(move |tx_id| async move { TX_FETCHER.fetch(&tx_id, testnet, false).await })
+            .await
+    }
 
-        let no_inputs = VarInt::try_from(self.inputs.len())?;
-        let no_inputs_bytes = no_inputs.serialize().into_iter();
+    /// Fee of this transaction, the previous transactions are got with `fetch`
+    pub(crate) async fn fee_with<F, Fut>(&self, fetch: F) -> Result<u64>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<Tx>> + Send + 'static,
+    {
+        let input_sum = self.total_input_value_with(fetch).await?;
+        let output_sum = self.total_output_value()?;
 
-        let no_outputs = VarInt::try_from(self.outputs.len())?;
-        let no_outputs_bytes = no_outputs.serialize().into_iter();
+        input_sum
+            .checked_sub(output_sum)
+            .ok_or(Error::InvalidTransaction("outputs exceed inputs"))
+    }
 
-        let inputs_bytes = self
-            .inputs
+    /// Get the legacy signature hash (`SIGHASH_ALL`) of the input at `index`,
+    /// `script_pubkey` being the one of the output it spends. Only what follows
+    /// its last `OP_CODESEPARATOR` is committed to, see
+    /// [`Tx::sig_hash_for_signature`] to also remove the signature pushes.
+    pub fn sig_hash(&self, index: usize, script_pubkey: &Script) -> Result<[u8; 32]> {
+        if index >= self.inputs.len() {
+            return Err(Error::InvalidTransaction("input index out of range"));
+        }
+
+        let (midstate, blank_inputs, suffix) = self.sig_hash_parts()?;
+        self.sig_hash_from_parts(&midstate, &blank_inputs, &suffix, index, script_pubkey)
+    }
+
+    /// Get the legacy signature hash the pushed `signature` (DER plus sighash
+    /// byte) commits to, i.e. over [`Script::sig_hash_subscript`], which drops
+    /// the pushes of the signature itself from `script_pubkey` (`FindAndDelete`)
+    pub fn sig_hash_for_signature(
+        &self,
+        index: usize,
+        script_pubkey: &Script,
+        signature: &[u8],
+    ) -> Result<[u8; 32]> {
+        self.sig_hash(index, &script_pubkey.sig_hash_subscript(signature))
+    }
+
+    /// Get the legacy signature hashes (`SIGHASH_ALL`) of every input, given
+    /// the script pubkeys they spend, in the same order. The shared parts are
+    /// only serialized (and hashed) once.
+    pub fn all_sig_hashes(&self, prevouts: &[Script]) -> Result<Vec<[u8; 32]>> {
+        if prevouts.len() != self.inputs.len() {
+            return Err(Error::InvalidTransaction("prevouts don't match inputs"));
+        }
+
+        let (midstate, blank_inputs, suffix) = self.sig_hash_parts()?;
+        prevouts
             .iter()
-            .map(|input| input.serialize())
-            .collect::<Result<Vec<_>, _>>()?
-            .into_iter()
-            .flatten();
+            .enumerate()
+            .map(|(index, script_pubkey)| {
+                self.sig_hash_from_parts(&midstate, &blank_inputs, &suffix, index, script_pubkey)
+            })
+            .collect()
+    }
+
+    /// Get the pieces shared by the signature hashes of all the inputs: the
+    /// hasher state after everything before the inputs, each input with an
+    /// empty script sig and everything after the inputs
+    fn sig_hash_parts(&self) -> Result<(Sha256, Vec<Vec<u8>>, Vec<u8>)> {
+        let mut midstate = Sha256::new();
+        midstate.update(self.version.to_le_bytes());
+        midstate.update(VarInt::try_from(self.inputs.len())?.serialize());
 
-        let outputs_bytes = self
-            .outputs
+        let blank_inputs = self
+            .inputs
             .iter()
-            .map(|output| output.serialize())
-            .collect::<Result<Vec<_>, _>>()?
-            .into_iter()
-            .flatten();
+            .map(|input| Self::with_script_sig(input, Script::new()).serialize())
+            .collect::<Result<Vec<_>>>()?;
 
-        let locktime_bytes = self.locktime.to_le_bytes();
+        let mut suffix = VarInt::try_from(self.outputs.len())?.serialize();
+        for output in &self.outputs {
+            suffix.extend(output.serialize()?);
+        }
+        suffix.extend(&self.locktime.to_le_bytes());
+        suffix.extend(&Self::SIGHASH_ALL.to_le_bytes());
+
+        Ok((midstate, blank_inputs, suffix))
+    }
+
+    fn sig_hash_from_parts(
+        &self,
+        midstate: &Sha256,
+        blank_inputs: &[Vec<u8>],
+        suffix: &[u8],
+        index: usize,
+        script_pubkey: &Script,
+    ) -> Result<[u8; 32]> {
+        let script_code = Script::from(script_pubkey.after_code_separator().to_vec());
+        let signed = Self::with_script_sig(&self.inputs[index], script_code);
+
+        let mut hasher = midstate.clone();
+        for (i, blank_input) in blank_inputs.iter().enumerate() {
+            if i == index {
+                hasher.update(signed.serialize()?);
+            } else {
+                hasher.update(blank_input);
+            }
+        }
+        hasher.update(suffix);
+
+        Ok(Sha256::digest(&hasher.finalize()).into())
+    }
+
+    fn with_script_sig(input: &Input, script_sig: Script) -> Input {
+        Input {
+            script_sig,
+            ..input.clone()
+        }
+    }
 
-        let result = version_bytes
+    /// Get the weight of this transaction (BIP141), witness data (along with
+    /// the segwit marker and flag) counts a quarter of the rest
+    pub fn weight(&self) -> Result<usize> {
+        let base_size = self.serialize()?.len();
+
+        let witness_size = if self.has_witness() {
+            let witnesses = self
+                .inputs
+                .iter()
+                .map(|input| Ok(input.witness.serialize()?.len()))
+                .sum::<Result<usize>>()?;
+
+            2 + witnesses
+        } else {
+            0
+        };
+
+        Ok(base_size * 4 + witness_size)
+    }
+
+    /// Get the virtual size of this transaction, its weight divided by 4
+    pub fn vsize(&self) -> Result<usize> {
+        Ok(self.weight()?.div_ceil(4))
+    }
+
+    /// Estimate the size of this transaction once signed, assuming every input
+    /// without a script sig will spend a p2pkh output. Witnesses aren't taken
+    /// into account, so the virtual size is the same as the size.
+    pub fn estimated_vsize(&self) -> Result<usize> {
+        let unsigned = self
+            .inputs
             .iter()
-            .copied()
-            .chain(no_inputs_bytes)
-            .chain(inputs_bytes)
-            .chain(no_outputs_bytes)
-            .chain(outputs_bytes)
-            .chain(locktime_bytes.iter().copied())
-            .collect();
+            .filter(|input| input.script_sig.cmds.is_empty())
+            .count();
+
+        // the script sig length still fits in a single byte varint
+        Ok(self.serialize()?.len() + unsigned * Self::P2PKH_SCRIPT_SIG_SIZE)
+    }
+
+    /// Fee needed to pay `sat_per_vbyte` for this transaction once signed, see
+    /// [`Tx::estimated_vsize`]
+    pub fn required_fee(&self, sat_per_vbyte: u64) -> Result<u64> {
+        (self.estimated_vsize()? as u64)
+            .checked_mul(sat_per_vbyte)
+            .ok_or(Error::AmountOverflow)
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        let mut result = Vec::new();
+        self.write_to(&mut result)?;
+        Ok(result)
+    }
 
+    /// Serialize this transaction along with its witnesses (BIP144), which is
+    /// the same as [`Tx::serialize`] if there are none
+    pub fn serialize_with_witness(&self) -> Result<Vec<u8>> {
+        let mut result = Vec::new();
+        self.write_fields(&mut result, self.has_witness())?;
         Ok(result)
     }
 
+    /// Write the serialization of this transaction (legacy format) into `w`,
+    /// field by field
+    pub fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        self.write_fields(w, false)
+    }
+
+    fn write_fields<W: Write>(&self, w: &mut W, with_witness: bool) -> Result<()> {
+        w.write_u32::<LittleEndian>(self.version)?;
+        if with_witness {
+            w.write_all(&[0x00, 0x01])?; // marker and flag
+        }
+
+        w.write_all(&VarInt::try_from(self.inputs.len())?.serialize())?;
+        for input in &self.inputs {
+            w.write_all(&input.serialize()?)?;
+        }
+
+        w.write_all(&VarInt::try_from(self.outputs.len())?.serialize())?;
+        for output in &self.outputs {
+            w.write_all(&output.serialize()?)?;
+        }
+
+        if with_witness {
+            for input in &self.inputs {
+                w.write_all(&input.witness.serialize()?)?;
+            }
+        }
+
+        w.write_u32::<LittleEndian>(self.locktime)?;
+        Ok(())
+    }
+
+    fn has_witness(&self) -> bool {
+        self.inputs.iter().any(|input| !input.witness.is_empty())
+    }
+
+    /// Check whether `bytes` look like a segwit serialized transaction, i.e.
+    /// the version is followed by the marker (0x00) and flag (0x01)
+    pub fn is_segwit_bytes(bytes: &[u8]) -> bool {
+        matches!(bytes.get(4..6), Some([0x00, 0x01]))
+    }
+
+    /// Parse a transaction that must span exactly all of `bytes`, failing on
+    /// trailing bytes (as well as on truncated ones)
+    pub fn deserialize_strict(bytes: &[u8], testnet: bool) -> Result<Self> {
+        let mut remaining = bytes;
+        let tx = Self::deserialize(&mut remaining, testnet)?;
+
+        if remaining.is_empty() {
+            Ok(tx)
+        } else {
+            Err(Error::InvalidTransaction("trailing bytes"))
+        }
+    }
+
+    /// Read `count` inputs from `reader` one at a time instead of collecting
+    /// them all, e.g. to validate huge transactions with bounded memory. The
+    /// reader must be positioned at the first input, witnesses aren't read.
+    pub fn inputs_from_reader<R: Read>(
+        mut reader: R,
+        count: u64,
+    ) -> impl Iterator<Item = Result<Input>> {
+        // the reader can't be trusted after an error, so that's the last item
+        let mut failed = false;
+        (0..count).map_while(move |_| {
+            if failed {
+                return None;
+            }
+
+            let input = Self::read_input(&mut reader);
+            failed = input.is_err();
+            Some(input)
+        })
+    }
+
+    fn read_input<R: Read>(reader: &mut R) -> Result<Input> {
+        // outpoint (36 bytes) and the first byte of the script length
+        let mut bytes = vec![0x00; 37];
+        reader.read_exact(&mut bytes)?;
+
+        let extra = match bytes[36] {
+            0xfd => 2,
+            0xfe => 4,
+            0xff => 8,
+            _ => 0,
+        };
+        let mut length = vec![bytes[36]; 1 + extra];
+        reader.read_exact(&mut length[1..])?;
+        bytes.extend_from_slice(&length[1..]);
+
+        // script and sequence, reading through `take` so an untrusted length
+        // can't make us allocate more than what the reader actually has
+        let (length, _) = VarInt::from_slice(&length)?;
+        let wanted = length.as_u64().saturating_add(4);
+        let expected = (bytes.len() as u64).saturating_add(wanted);
+        reader.take(wanted).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != expected {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+
+        Input::deserialize(bytes.as_slice())
+    }
+
+    /// Deserialize either a legacy or a segwit (BIP144) transaction
     pub fn deserialize(buf: impl Buf, testnet: bool) -> Result<Self> {
         let mut reader = buf.reader();
 
         let version = reader.read_u32::<LittleEndian>()?;
 
-        let no_inputs = VarInt::deserialize(reader.get_mut())?;
-        let inputs: Vec<_> = (0..no_inputs.as_u64())
+        // Segwit transactions have a marker (0x00) where the number of inputs
+        // would be, followed by a flag (0x01). Any other byte after a zero means
+        // a legacy transaction without inputs, which is invalid.
+        let mut no_inputs = VarInt::deserialize(reader.get_mut())?;
+        let segwit = no_inputs.as_u64() == 0;
+        if segwit {
+            if reader.read_u8()? != 0x01 {
+                return Err(Error::InvalidTransaction("no inputs"));
+            }
+
+            no_inputs = VarInt::deserialize(reader.get_mut())?;
+            if no_inputs.as_u64() == 0 {
+                return Err(Error::InvalidTransaction("no inputs"));
+            }
+        }
+
+        let mut inputs: Vec<_> = (0..no_inputs.as_u64())
             .map(|_| Input::deserialize(reader.get_mut()))
             .collect::<Result<_, _>>()?;
 
@@ -97,7 +614,13 @@ impl Tx {
             .map(|_| Output::deserialize(reader.get_mut()))
             .collect::<Result<_, _>>()?;
 
-        let locktime = reader.read_u64::<LittleEndian>()?;
+        if segwit {
+            for input in &mut inputs {
+                input.witness = Witness::deserialize(reader.get_mut())?;
+            }
+        }
+
+        let locktime = reader.read_u32::<LittleEndian>()?;
 
         Ok(Self {
             version,
@@ -105,6 +628,822 @@ impl Tx {
             outputs,
             locktime,
             testnet,
+            cached_hash: default(),
         })
     }
 }
+
+/// Helper to assemble transactions paying to addresses
+#[derive(Debug, Clone)]
+pub struct TxBuilder {
+    version: u32,
+    inputs: Vec<Input>,
+    outputs: Vec<Output>,
+    change: Option<Output>,
+    locktime: u32,
+    testnet: bool,
+}
+
+impl TxBuilder {
+    pub fn new(testnet: bool) -> Self {
+        Self {
+            version: 1,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            change: None,
+            locktime: 0,
+            testnet,
+        }
+    }
+
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn locktime(mut self, locktime: u32) -> Self {
+        self.locktime = locktime;
+        self
+    }
+
+    pub fn input(mut self, input: Input) -> Self {
+        self.inputs.push(input);
+        self
+    }
+
+    pub fn output(mut self, output: Output) -> Self {
+        self.outputs.push(output);
+        self
+    }
+
+    /// Append an output paying `amount` to `address`
+    pub fn send_to(self, address: &str, amount: u64) -> Result<Self> {
+        let script_pubkey = Script::for_address(address)?;
+        Ok(self.output(Output {
+            amount,
+            script_pubkey,
+        }))
+    }
+
+    /// Set the change output, it's always placed after every other output
+    pub fn change_to(mut self, address: &str, amount: u64) -> Result<Self> {
+        let script_pubkey = Script::for_address(address)?;
+        self.change = Some(Output {
+            amount,
+            script_pubkey,
+        });
+
+        Ok(self)
+    }
+
+    pub fn build(self) -> Tx {
+        let mut outputs = self.outputs;
+        outputs.extend(self.change);
+
+        Tx {
+            version: self.version,
+            inputs: self.inputs,
+            outputs,
+            locktime: self.locktime,
+            testnet: self.testnet,
+            cached_hash: default(),
+        }
+    }
+}
+
+/// Sort transactions along with their fees (in satoshis) by fee rate, highest
+/// first, as done when picking transactions for a block template
+pub fn sort_by_fee_rate(txs: &mut [(Tx, u64)]) -> Result<()> {
+    let mut keyed = txs
+        .iter()
+        .enumerate()
+        .map(|(index, (tx, fee))| Ok((tx.vsize()? as u128, *fee as u128, index)))
+        .collect::<Result<Vec<_>>>()?;
+
+    // a/b > c/d <=> a*d > c*b, avoids rounding the rates
+    keyed.sort_by(|(a_vsize, a_fee, _), (b_vsize, b_fee, _)| {
+        (b_fee * a_vsize).cmp(&(a_fee * b_vsize))
+    });
+
+    // move the transactions in place instead of cloning them, the one for
+    // position `i` started at `order[i]` but may have been swapped away to a
+    // later position already, which is found by following the swaps
+    let order: Vec<_> = keyed.into_iter().map(|(_, _, index)| index).collect();
+    for i in 0..order.len() {
+        let mut source = order[i];
+        while source < i {
+            source = order[source];
+        }
+
+        txs.swap(i, source);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use hex_literal::hex;
+
+    use super::*;
+    use crate::secp256k1::crypto::PrivateKey;
+
+    #[test]
+    fn cached_hash() -> Result<()> {
+        let tx = Tx {
+            version: 1,
+            inputs: vec![],
+            outputs: vec![],
+            locktime: 0,
+            testnet: false,
+            cached_hash: default(),
+        };
+
+        let mut expected = hash256(tx.serialize()?);
+        expected.reverse();
+
+        assert!(tx.cached_hash.get().is_none());
+        assert_eq!(tx.hash()?, expected);
+        assert_eq!(tx.cached_hash.get(), Some(&expected));
+        assert_eq!(tx.id()?, hex::encode(&expected));
+
+        Ok(())
+    }
+
+    #[test]
+    fn consensus_equality() -> Result<()> {
+        let tx = TxBuilder::new(false)
+            .input(Input::new([0x01; 32], 0)?)
+            .locktime(100)
+            .build();
+
+        // cached hash and testnet flag don't matter
+        let mut other = tx.clone();
+        other.testnet = true;
+        other.hash()?;
+        assert_eq!(other, tx);
+
+        other.locktime = 101;
+        assert_ne!(other, tx);
+
+        Ok(())
+    }
+
+    #[test]
+    fn coinbase() -> Result<()> {
+        let key = PrivateKey::new(1usize)?;
+        let reward = Output {
+            amount: 50_0000_0000,
+            script_pubkey: Script::p2pkh(&key.public_key().hash160(true)?),
+        };
+
+        for &height in &[0u64, 1, 16, 17, 127, 128, 255, 256, 465879, 0x7fff_ffff] {
+            let tx = Tx::coinbase(height, vec![reward.clone()], None)?;
+            assert!(tx.is_coinbase());
+            assert_eq!(tx.coinbase_height(), Some(height));
+            assert!(tx.inputs[0].script_sig.serialize()?.len() > 2);
+
+            let reparsed = Tx::deserialize(tx.serialize()?.as_slice(), false)?;
+            assert_eq!(reparsed.coinbase_height(), Some(height));
+        }
+
+        // BIP34 example, block 227836
+        let tx = Tx::coinbase(227836, vec![reward.clone()], None)?;
+        assert_eq!(
+            tx.inputs[0].script_sig.cmds[0],
+            ScriptCommand::Element(Bytes::from(vec![0xfc, 0x79, 0x03]))
+        );
+
+        let tx = Tx::coinbase(500_000, vec![reward.clone()], Some([0xab; 32]))?;
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(
+            tx.outputs[1].script_pubkey.serialize()?[..7],
+            hex!("266a24aa21a9ed")
+        );
+        assert_eq!(tx.inputs[0].witness.len(), 1);
+
+        assert!(Tx::coinbase(1, vec![], None).is_err());
+        assert!(!TxBuilder::new(false)
+            .input(Input::new([0x01; 32], 0)?)
+            .build()
+            .is_coinbase());
+
+        Ok(())
+    }
+
+    #[test]
+    fn witness_commitment() -> Result<()> {
+        // blocks with just the coinbase all share the same commitment
+        let coinbase = Tx::coinbase(1, vec![], Some([0x00; 32]))?;
+        assert_eq!(
+            Tx::witness_commitment(std::slice::from_ref(&coinbase))?,
+            hex!("e2f61c3f71d1defd3fa999dfa36953755c690689799962b48bebd836974e8cf9")
+        );
+
+        // BIP143 native P2WPKH example
+        let bytes = hex!(
+            "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f000000
+            00494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be0220
+            40529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804
+            cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb20600
+            0000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143b
+            de42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5
+            b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c
+            212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07ae
+            ee635711000000"
+        );
+        let tx = Tx::deserialize(&bytes[..], false)?;
+        let commitment = Tx::witness_commitment(&[coinbase, tx])?;
+        assert_eq!(
+            commitment,
+            hex!("c785e25a057716995d108eff9f98bec23813b9a5e5b6462b0ab4c1e4a071d61d")
+        );
+
+        // it's what goes in the coinbase output after the header
+        let coinbase = Tx::coinbase(1, vec![], Some(commitment))?;
+        assert_eq!(
+            coinbase.outputs[0].script_pubkey.serialize()?[7..],
+            commitment
+        );
+
+        assert!(Tx::witness_commitment(&[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn segwit_vsize() -> Result<()> {
+        let bytes = hex!(
+            "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f000000
+            00494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be0220
+            40529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804
+            cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb20600
+            0000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143b
+            de42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5
+            b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c
+            212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07ae
+            ee635711000000"
+        );
+
+        let tx = Tx::deserialize(&bytes[..], false)?;
+        let base_size = tx.serialize()?.len();
+
+        assert_eq!(tx.weight()?, base_size * 3 + bytes.len());
+        assert_eq!(tx.vsize()?, tx.weight()?.div_ceil(4));
+
+        let legacy = TxBuilder::new(false)
+            .input(Input::new([0x01; 32], 0)?)
+            .build();
+        assert_eq!(legacy.vsize()?, legacy.serialize()?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn fee_rate_order() -> Result<()> {
+        let key = PrivateKey::new(1usize)?;
+        let address = key.public_key().address(true, Network::Mainnet)?;
+
+        let tx_with_outputs = |n: usize| -> Result<Tx> {
+            let mut builder = TxBuilder::new(false).input(Input::new([n as u8; 32], 0)?);
+            for _ in 0..n {
+                builder = builder.send_to(&address, 1000)?;
+            }
+            Ok(builder.build())
+        };
+
+        let small = tx_with_outputs(1)?;
+        let large = tx_with_outputs(10)?;
+        assert!(large.vsize()? > 2 * small.vsize()?);
+
+        // same fee, but the large one has a much lower rate
+        let mut txs = vec![
+            (large.clone(), 1000),
+            (small.clone(), 1000),
+            (small.clone(), 100),
+            (large.clone(), 5000),
+        ];
+        sort_by_fee_rate(&mut txs)?;
+
+        let order: Vec<_> = txs
+            .iter()
+            .map(|(tx, fee)| (tx.outputs.len(), *fee))
+            .collect();
+        assert_eq!(order, vec![(10, 5000), (1, 1000), (10, 1000), (1, 100)]);
+
+        // longer permutation cycles end up in place too
+        let fees = [7, 3, 11, 0, 5, 9, 1, 12, 4, 8, 2, 10, 6];
+        let mut txs: Vec<_> = fees.iter().map(|fee| (small.clone(), *fee)).collect();
+        sort_by_fee_rate(&mut txs)?;
+
+        let sorted: Vec<_> = txs.iter().map(|(_, fee)| *fee).collect();
+        assert_eq!(sorted, (0..=12).rev().collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn total_output_value() -> Result<()> {
+        let output = |amount| Output {
+            amount,
+            script_pubkey: Script::new(),
+        };
+
+        let mut tx = Tx {
+            version: 1,
+            inputs: vec![],
+            outputs: vec![output(1000), output(2500), output(40)],
+            locktime: 0,
+            testnet: false,
+            cached_hash: default(),
+        };
+
+        assert_eq!(tx.total_output_value()?, 3540);
+
+        tx.outputs.push(output(u64::MAX));
+        assert!(matches!(
+            tx.total_output_value(),
+            Err(Error::AmountOverflow)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_legacy() -> Result<()> {
+        let bytes = hex!(
+            "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b
+            483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a98
+            6d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545
+            de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b
+            654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e33216
+            6702cb75f40df79fea1288ac19430600"
+        );
+
+        let tx = Tx::deserialize(&bytes[..], false)?;
+        assert_eq!(tx.version, 1);
+        assert_eq!(tx.inputs.len(), 1);
+        assert_eq!(
+            tx.inputs[0].prev_tx.as_ref(),
+            hex!("d1c789a9c60383bf715f3f6ad9d14b91fe55f3deb369fe5d9280cb1a01793f81")
+        );
+        assert_eq!(tx.inputs[0].script_sig.cmds.len(), 2);
+        assert_eq!(tx.inputs[0].sequence, 0xfffffffe);
+        assert!(tx.inputs[0].witness.is_empty());
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(tx.outputs[0].amount, 32454049);
+        assert_eq!(tx.outputs[1].amount, 10011545);
+        assert_eq!(tx.locktime, 410393);
+        assert_eq!(
+            tx.id()?,
+            "452c629d67e41baec3ac6f04fe744b4b9617f8f859c63b3002f8684e7a4fee03"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_segwit() -> Result<()> {
+        // BIP143 native P2WPKH example
+        let bytes = hex!(
+            "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f000000
+            00494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be0220
+            40529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804
+            cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb20600
+            0000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143b
+            de42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5
+            b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c
+            212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07ae
+            ee635711000000"
+        );
+
+        let tx = Tx::deserialize(&bytes[..], false)?;
+        assert_eq!(tx.version, 1);
+        assert_eq!(tx.inputs.len(), 2);
+        assert_eq!(tx.inputs[0].prev_idx, 0);
+        assert_eq!(tx.inputs[1].prev_idx, 1);
+        assert!(tx.inputs[0].witness.is_empty());
+        assert_eq!(tx.inputs[1].witness.len(), 2);
+        assert_eq!(
+            tx.inputs[1].witness.items()[1].as_ref(),
+            hex!("025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357")
+        );
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(tx.outputs[0].amount, 112340000);
+        assert_eq!(tx.outputs[1].amount, 223450000);
+        assert_eq!(tx.locktime, 17);
+        assert_eq!(
+            tx.id()?,
+            "e8151a2af31c368a35053ddd4bdb285a8595c769a3ad83e0fa02314a602d4609"
+        );
+
+        // the witnesses don't take part in the txid
+        let mut stripped = tx.clone();
+        for input in &mut stripped.inputs {
+            input.witness = Witness::new();
+        }
+        stripped.cached_hash = default();
+        assert_eq!(stripped.txid()?, tx.txid()?);
+        assert_eq!(tx.txid()?, tx.id()?);
+
+        assert_eq!(tx.serialize_with_witness()?, bytes);
+        assert_eq!(
+            tx.wtxid()?,
+            "c36c38370907df2324d9ce9d149d191192f338b37665a82e78e76a12c909b762"
+        );
+        assert_eq!(stripped.wtxid()?, stripped.txid()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn inputs_from_reader() -> Result<()> {
+        // BIP143 native P2WPKH example, its inputs start after the version,
+        // the segwit marker and flag and the number of inputs
+        let bytes = hex!(
+            "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f000000
+            00494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be0220
+            40529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804
+            cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb20600
+            0000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143b
+            de42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5
+            b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c
+            212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07ae
+            ee635711000000"
+        );
+        let tx = Tx::deserialize(&bytes[..], false)?;
+
+        let reader = io::BufReader::new(&bytes[7..]);
+        let inputs = Tx::inputs_from_reader(reader, 2).collect::<crate::Result<Vec<_>>>()?;
+        assert_eq!(inputs.len(), 2);
+        for (read, parsed) in inputs.iter().zip(&tx.inputs) {
+            assert_eq!(read.prev_tx, parsed.prev_tx);
+            assert_eq!(read.prev_idx, parsed.prev_idx);
+            assert_eq!(read.script_sig, parsed.script_sig);
+            assert_eq!(read.sequence, parsed.sequence);
+            assert!(read.witness.is_empty());
+        }
+
+        // a truncated input is an error and ends the iteration
+        let reader = io::BufReader::new(&bytes[7..150]);
+        let mut inputs = Tx::inputs_from_reader(reader, 2);
+        assert!(inputs.next().unwrap().is_ok());
+        assert!(inputs.next().unwrap().is_err());
+        assert!(inputs.next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn relative_locktimes() -> Result<()> {
+        let prev_tx = [0x01; 32];
+        let mut blocks = Input::new(prev_tx, 0)?;
+        blocks.sequence = 144;
+        let mut time = Input::new(prev_tx, 1)?;
+        time.sequence = (1 << 22) | 2;
+        let mut disabled = Input::new(prev_tx, 2)?;
+        disabled.sequence = (1 << 31) | 144;
+
+        let tx = TxBuilder::new(false)
+            .version(2)
+            .input(blocks)
+            .input(time)
+            .input(disabled)
+            .build();
+
+        assert_eq!(tx.relative_locktime(0), Some(RelativeLockTime::Blocks(144)));
+        assert_eq!(tx.relative_locktime(1), Some(RelativeLockTime::Time(1024)));
+        assert_eq!(tx.relative_locktime(2), None);
+        assert_eq!(tx.relative_locktime(3), None);
+
+        // sequences have no relative lock meaning before version 2
+        let mut tx = tx;
+        tx.version = 1;
+        assert_eq!(tx.relative_locktime(0), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_sig_hashes() -> Result<()> {
+        let bytes = hex!(
+            "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b
+            483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a98
+            6d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545
+            de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b
+            654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e33216
+            6702cb75f40df79fea1288ac19430600"
+        );
+        let tx = Tx::deserialize(&bytes[..], false)?;
+
+        let prev_script = Script::p2pkh(&hex!("a802fc56c704ce87c42d7c92eb75e7896bdc41ae"));
+        assert_eq!(
+            tx.sig_hash(0, &prev_script)?,
+            hex!("27e0c5994dec7824e56dec6b2fcb342eb7cdb0d0957c2fce9882f715e85d81a6")
+        );
+        assert!(tx.sig_hash(1, &prev_script).is_err());
+
+        let prev_tx = hex!("d1c789a9c60383bf715f3f6ad9d14b91fe55f3deb369fe5d9280cb1a01793f81");
+        let outputs = tx.outputs.clone();
+        let tx = outputs
+            .into_iter()
+            .fold(TxBuilder::new(false), TxBuilder::output)
+            .input(Input::new(prev_tx, 0)?)
+            .input(Input::new(prev_tx, 1)?)
+            .input(Input::new(prev_tx, 2)?)
+            .build();
+
+        let prevouts: Vec<_> = (1..=3u8).map(|i| Script::p2pkh(&[i; 20])).collect();
+        let batch = tx.all_sig_hashes(&prevouts)?;
+        for (index, script_pubkey) in prevouts.iter().enumerate() {
+            assert_eq!(batch[index], tx.sig_hash(index, script_pubkey)?);
+        }
+        assert_ne!(batch[0], batch[1]);
+        assert!(tx.all_sig_hashes(&prevouts[..2]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn code_separator_sig_hashes() -> Result<()> {
+        let bytes = hex!(
+            "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b
+            483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a98
+            6d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545
+            de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b
+            654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e33216
+            6702cb75f40df79fea1288ac19430600"
+        );
+        let tx = Tx::deserialize(&bytes[..], false)?;
+
+        let signature = Bytes::from_static(&hex!("300602010102010101"));
+        let pubkey = Bytes::from(vec![0x02; 33]);
+
+        // <sig> OP_DROP OP_CODESEPARATOR <sig> OP_DROP <pubkey> OP_CHECKSIG
+        let script = Script::from(vec![
+            ScriptCommand::Element(signature.clone()),
+            ScriptCommand::Other(0x75),
+            ScriptCommand::Other(0xab),
+            ScriptCommand::Element(signature.clone()),
+            ScriptCommand::Other(0x75),
+            ScriptCommand::Element(pubkey),
+            ScriptCommand::OpCheckSig,
+        ]);
+
+        // only what follows the separator is committed to
+        assert_eq!(
+            tx.sig_hash(0, &script)?,
+            hex!("78d8131df54fae4d221030f6c79532745fd7ef712f62e4ecd851c39b378a0915")
+        );
+
+        // and the signature being checked is removed as well
+        assert_eq!(
+            tx.sig_hash_for_signature(0, &script, &signature)?,
+            hex!("0f05162702b4303a11b0bebc8b676e7e9a7ae72aa7bfe73b03121f4afc63d62e")
+        );
+        assert_eq!(
+            tx.sig_hash_for_signature(0, &script, &signature)?,
+            tx.sig_hash(0, &script.sig_hash_subscript(&signature))?
+        );
+
+        // batches agree
+        assert_eq!(
+            tx.all_sig_hashes(std::slice::from_ref(&script))?[0],
+            tx.sig_hash(0, &script)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn segwit_bytes_detection() {
+        // version 1, a single input spending d1c7...3f81:0
+        let legacy =
+            hex!("0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1");
+        assert!(!Tx::is_segwit_bytes(&legacy));
+
+        // version 1, marker, flag and two inputs
+        let segwit = hex!("01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf4");
+        assert!(Tx::is_segwit_bytes(&segwit));
+
+        assert!(!Tx::is_segwit_bytes(&segwit[..5]));
+        assert!(!Tx::is_segwit_bytes(&[]));
+        assert!(!Tx::is_segwit_bytes(&hex!("010000000002")));
+    }
+
+    #[test]
+    fn deserialize_without_inputs() {
+        // legacy: version, no inputs, no outputs, locktime
+        let legacy = hex!("01000000000000000000");
+        let result = Tx::deserialize(&legacy[..], false);
+        assert!(matches!(
+            result,
+            Err(Error::InvalidTransaction("no inputs"))
+        ));
+
+        // segwit: version, marker, flag, no inputs, no outputs, locktime
+        let segwit = hex!("010000000001000000000000");
+        let result = Tx::deserialize(&segwit[..], false);
+        assert!(matches!(
+            result,
+            Err(Error::InvalidTransaction("no inputs"))
+        ));
+    }
+
+    #[test]
+    fn send_to_recipients() -> Result<()> {
+        let keys = (1usize..=3)
+            .map(PrivateKey::new)
+            .collect::<crate::Result<Vec<_>>>()?;
+        let addresses = keys
+            .iter()
+            .map(|key| key.public_key().address(true, Network::Testnet))
+            .collect::<crate::Result<Vec<_>>>()?;
+
+        let prev_tx = hex!("d1c789a9c60383bf715f3f6ad9d14b91fe55f3deb369fe5d9280cb1a01793f81");
+        let tx = TxBuilder::new(true)
+            .input(Input::new(prev_tx, 0)?)
+            .change_to(&addresses[2], 500)?
+            .send_to(&addresses[0], 1000)?
+            .send_to(&addresses[1], 2000)?
+            .build();
+
+        assert_eq!(tx.inputs.len(), 1);
+        assert_eq!(tx.outputs.len(), 3);
+        assert_eq!(tx.total_output_value()?, 3500);
+
+        let amounts = [1000, 2000, 500];
+        for ((output, key), amount) in tx.outputs.iter().zip(&keys).zip(&amounts) {
+            let hash = key.public_key().hash160(true)?;
+
+            assert_eq!(output.amount, *amount);
+            assert_eq!(output.script_pubkey.cmds, Script::p2pkh(&hash).cmds);
+        }
+
+        assert!(TxBuilder::new(true).send_to("not an address", 1).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn estimated_size() -> Result<()> {
+        let key = PrivateKey::new(8675309usize)?;
+        let address = key.public_key().address(true, Network::Testnet)?;
+
+        let prev_tx = hex!("d1c789a9c60383bf715f3f6ad9d14b91fe55f3deb369fe5d9280cb1a01793f81");
+        let mut tx = TxBuilder::new(true)
+            .input(Input::new(prev_tx, 0)?)
+            .input(Input::new(prev_tx, 1)?)
+            .send_to(&address, 1000)?
+            .change_to(&address, 500)?
+            .build();
+
+        let estimated = tx.estimated_vsize()?;
+
+        let sec = key.public_key().serialize(true)?;
+        for (i, input) in tx.inputs.iter_mut().enumerate() {
+            let digest = hash256([i as u8]);
+            let signature = key.create_signature(&digest)?;
+            let signature = signature.serialize_with_sighash(0x01)?; // SIGHASH_ALL
+
+            input.script_sig = Script::from(vec![
+                ScriptCommand::Element(signature.into()),
+                ScriptCommand::Element(Bytes::copy_from_slice(&sec)),
+            ]);
+        }
+
+        // signatures may be a couple of bytes shorter than the estimate
+        let actual = tx.serialize()?.len();
+        assert!(actual <= estimated);
+        assert!(estimated - actual <= 2 * tx.inputs.len());
+        assert_eq!(tx.estimated_vsize()?, actual);
+
+        Ok(())
+    }
+
+    #[test]
+    fn required_fee() -> Result<()> {
+        let key = PrivateKey::new(8675309usize)?;
+        let address = key.public_key().address(true, Network::Testnet)?;
+
+        let prev_tx = hex!("d1c789a9c60383bf715f3f6ad9d14b91fe55f3deb369fe5d9280cb1a01793f81");
+        let tx = TxBuilder::new(true)
+            .input(Input::new(prev_tx, 0)?)
+            .send_to(&address, 1000)?
+            .change_to(&address, 500)?
+            .build();
+
+        // the usual 226 vbytes of a p2pkh 1-in-2-out transaction
+        assert_eq!(tx.required_fee(1)?, 226);
+        assert_eq!(tx.required_fee(10)?, 2260);
+        assert_eq!(tx.required_fee(25)?, 5650);
+        assert_eq!(tx.required_fee(0)?, 0);
+        assert!(matches!(
+            tx.required_fee(u64::MAX),
+            Err(Error::AmountOverflow)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn input_value_fetches_distinct_txs() -> Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let prev_tx = |amounts: &[u64]| Tx {
+            version: 1,
+            inputs: vec![],
+            outputs: amounts
+                .iter()
+                .map(|amount| Output {
+                    amount: *amount,
+                    script_pubkey: Script::new(),
+                })
+                .collect(),
+            locktime: 0,
+            testnet: false,
+            cached_hash: default(),
+        };
+
+        let (a, b) = ([0x0a; 32], [0x0b; 32]);
+        let prev_txs: HashMap<_, _> = vec![
+            (hex::encode(a), prev_tx(&[1000, 2000])),
+            (hex::encode(b), prev_tx(&[300])),
+        ]
+        .into_iter()
+        .collect();
+        let prev_txs = Arc::new(prev_txs);
+
+        let tx = TxBuilder::new(false)
+            .input(Input::new(a, 0)?)
+            .input(Input::new(b, 0)?)
+            .input(Input::new(a, 1)?)
+            .input(Input::new(b, 0)?)
+            .input(Input::new(a, 0)?)
+            .build();
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+
+        let fetch = |tx_id: String| {
+            let prev_txs = prev_txs.clone();
+            let calls = calls.clone();
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+
+            async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+
+                tokio::task::yield_now().await;
+
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok(prev_txs[&tx_id].clone())
+            }
+        };
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+        let total = runtime.block_on(tx.total_input_value_with(fetch))?;
+
+        assert_eq!(total, 1000 + 300 + 2000 + 300 + 1000);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn output_addresses() -> Result<()> {
+        let p2pkh = Output {
+            amount: 1000,
+            script_pubkey: Script::p2pkh(&hex!("751e76e8199196d454941c45d1b3a323f1433bd6")),
+        };
+        let op_return = Output {
+            amount: 0,
+            script_pubkey: Script::from(vec![
+                ScriptCommand::OpReturn,
+                ScriptCommand::Element(Bytes::from_static(b"oxicoin")),
+            ]),
+        };
+
+        let tx = TxBuilder::new(false)
+            .input(Input::new([0x01; 32], 0)?)
+            .output(p2pkh)
+            .output(op_return)
+            .build();
+
+        assert_eq!(
+            tx.output_addresses(Network::Mainnet),
+            [Some("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH".to_string()), None]
+        );
+        assert_eq!(
+            tx.output_addresses(Network::Testnet),
+            [Some("mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r".to_string()), None]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+use std::convert::TryFrom;
+use std::io::Read;
+
+use bytes::{Buf, Bytes};
+
+use crate::varint::VarInt;
+use crate::{Error, Result};
+
+/// Witness of a segwit input, a stack of arbitrary byte items
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Witness {
+    pub(crate) items: Vec<Bytes>,
+}
+
+impl From<Vec<Bytes>> for Witness {
+    fn from(items: Vec<Bytes>) -> Self {
+        Self { items }
+    }
+}
+
+impl Witness {
+    pub fn new() -> Self {
+        Self { items: Vec::new() }
+    }
+
+    pub fn items(&self) -> &[Bytes] {
+        &self.items
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        let mut result = VarInt::try_from(self.items.len())?.serialize();
+
+        for item in &self.items {
+            result.extend(VarInt::try_from(item.len())?.serialize());
+            result.extend_from_slice(item);
+        }
+
+        Ok(result)
+    }
+
+    pub fn deserialize(buf: impl Buf) -> Result<Self> {
+        let mut reader = buf.reader();
+
+        let no_items = VarInt::deserialize(reader.get_mut())?;
+        let items = (0..no_items.as_u64())
+            .map(|_| {
+                // the length is untrusted, don't allocate more than what's left
+                let length = VarInt::deserialize(reader.get_mut())?.as_u64();
+                if length > reader.get_ref().remaining() as u64 {
+                    return Err(Error::InvalidTransaction("witness item too long"));
+                }
+
+                let mut item = vec![0u8; length as usize];
+                reader.read_exact(&mut item)?;
+                Ok(Bytes::from(item))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { items })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use hex_literal::hex;
+
+    use super::*;
+
+    #[test]
+    fn empty_witness() -> Result<()> {
+        let witness = Witness::new();
+        assert_eq!(witness.serialize()?, vec![0x00]);
+        assert_eq!(Witness::deserialize(&[0x00][..])?, witness);
+
+        Ok(())
+    }
+
+    #[test]
+    fn two_items_witness() -> Result<()> {
+        let bytes = hex!("0203aabbcc0201ff");
+        let witness = Witness::deserialize(&bytes[..])?;
+
+        assert_eq!(witness.len(), 2);
+        assert_eq!(witness.items()[0].as_ref(), hex!("aabbcc"));
+        assert_eq!(witness.items()[1].as_ref(), hex!("01ff"));
+        assert_eq!(witness.serialize()?, bytes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_witness() {
+        let bytes = hex!("0203aabb");
+        assert!(Witness::deserialize(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn oversized_item_length() {
+        // an item claiming 2^64 - 1 bytes must fail instead of allocating
+        let bytes = hex!("01ffffffffffffffffff00");
+        assert!(matches!(
+            Witness::deserialize(&bytes[..]),
+            Err(Error::InvalidTransaction(_))
+        ));
+    }
+}
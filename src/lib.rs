@@ -3,6 +3,7 @@ mod macros;
 pub mod base58;
 pub mod core;
 mod format;
+pub mod network;
 pub mod secp256k1;
 pub mod utils;
 pub mod varint;
@@ -28,6 +29,12 @@ pub enum Error {
         source: hyper::Error,
     },
 
+    #[error("hex error: {source}")]
+    HexError {
+        #[from]
+        source: hex::FromHexError,
+    },
+
     #[error("int to big for varint")]
     IntToBigForVarInt,
 
@@ -37,6 +44,9 @@ pub enum Error {
     #[error("point is not on the curve")]
     PointNotOnTheCurve,
 
+    #[error("number is not a reduced element of the field")]
+    NotInField,
+
     #[error("overflow error while padding")]
     OverflowPadding,
 
@@ -49,11 +59,62 @@ pub enum Error {
     #[error("invalid sec bytes, expecting either 33 or 65 bytes, got {0} ")]
     InvalidSecBytesLength(usize),
 
+    #[error("invalid sec prefix {0:#04x}")]
+    InvalidSecPrefix(u8),
+
+    #[error("invalid previous tx, expecting 32 bytes, got {0}")]
+    InvalidPrevTxLength(usize),
+
+    #[error("invalid base58 character: {0}")]
+    InvalidBase58Character(char),
+
+    #[error("invalid base58 checksum")]
+    InvalidBase58Checksum,
+
+    #[error("cannot base58 encode an empty payload with checksum")]
+    EmptyBase58Payload,
+
+    #[error("invalid base58 alphabet, expecting 58 distinct ascii characters")]
+    InvalidBase58Alphabet,
+
+    #[error("invalid wif ({0})")]
+    InvalidWif(&'static str),
+
+    #[error("invalid address ({0})")]
+    InvalidAddress(&'static str),
+
+    #[error("invalid nonce, must be in the range [1, N)")]
+    InvalidNonce,
+
+    #[error("invalid secret, must be in the range [1, N)")]
+    InvalidSecret,
+
+    #[error("invalid secret bytes, expecting 32 bytes, got {0}")]
+    InvalidSecretLength(usize),
+
     #[error("invalid signature ({0})")]
     InvalidSignature(&'static str),
 
+    #[error("invalid script ({0})")]
+    InvalidScript(&'static str),
+
+    #[error("invalid transaction ({0})")]
+    InvalidTransaction(&'static str),
+
+    #[error("invalid block header ({0})")]
+    InvalidBlockHeader(&'static str),
+
+    #[error("output index {0} is out of range")]
+    OutputIndexOutOfRange(u32),
+
+    #[error("amount overflow")]
+    AmountOverflow,
+
     #[error("fetched invalid transaction")]
     FetchedInvalidTransaction,
+
+    #[error("fetching failed with http status {0}")]
+    FetchHttpStatus(u16),
 }
 
 impl Error {
@@ -0,0 +1,97 @@
+use crate::base58;
+use crate::{Error, Result};
+
+/// Bitcoin networks, mainly differing in the version bytes used for
+/// addresses and private keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Regtest,
+    Signet,
+}
+
+/// Version bytes used by a [`Network`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetworkParams {
+    /// Prefix of pay-to-pubkey-hash addresses
+    pub p2pkh_prefix: u8,
+    /// Prefix of pay-to-script-hash addresses
+    pub p2sh_prefix: u8,
+    /// Prefix of private keys in the WIF format
+    pub wif_prefix: u8,
+}
+
+const MAINNET_PARAMS: NetworkParams = NetworkParams {
+    p2pkh_prefix: 0x00,
+    p2sh_prefix: 0x05,
+    wif_prefix: 0x80,
+};
+
+// regtest and signet share the testnet version bytes
+const TESTNET_PARAMS: NetworkParams = NetworkParams {
+    p2pkh_prefix: 0x6f,
+    p2sh_prefix: 0xc4,
+    wif_prefix: 0xef,
+};
+
+impl Network {
+    /// Map the legacy `testnet` flag to a network
+    pub const fn from_testnet(testnet: bool) -> Self {
+        if testnet {
+            Self::Testnet
+        } else {
+            Self::Mainnet
+        }
+    }
+
+    pub const fn params(self) -> NetworkParams {
+        match self {
+            Self::Mainnet => MAINNET_PARAMS,
+            Self::Testnet | Self::Regtest | Self::Signet => TESTNET_PARAMS,
+        }
+    }
+}
+
+/// Get the network of a (base58) p2pkh or p2sh address, regtest and signet
+/// addresses can't be told apart from testnet ones
+pub fn address_network(addr: &str) -> Result<Network> {
+    let decoded = base58::decode_checksum(addr)?;
+    if decoded.len() != 21 {
+        return Err(Error::InvalidAddress("expecting 21 bytes"));
+    }
+
+    let prefix = decoded[0];
+    [Network::Mainnet, Network::Testnet]
+        .iter()
+        .copied()
+        .find(|network| {
+            let params = network.params();
+            params.p2pkh_prefix == prefix || params.p2sh_prefix == prefix
+        })
+        .ok_or(Error::InvalidAddress("unknown prefix"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn network_of_addresses() -> Result<()> {
+        let mainnet = address_network("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH")?;
+        assert_eq!(mainnet, Network::Mainnet);
+        assert_eq!(
+            address_network("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy")?,
+            Network::Mainnet
+        );
+
+        let testnet = address_network("mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r")?;
+        assert_eq!(testnet, Network::Testnet);
+
+        // a WIF has an unknown prefix and a different length
+        let wif = "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn";
+        assert!(address_network(wif).is_err());
+
+        Ok(())
+    }
+}
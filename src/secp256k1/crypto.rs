@@ -1,24 +1,43 @@
+use std::convert::TryFrom;
+
+use derivative::Derivative;
 use hmac::{Hmac, Mac, NewMac};
 use num_bigint::BigUint;
-use num_traits::One;
+use num_traits::{One, Zero};
 use sha2::Sha256;
 
-use crate::utils::{hash160, prepend_padding, Chain};
+use crate::network::Network;
+use crate::utils::{hash160, hash256, prepend_padding, Chain};
 use crate::{base58, Error, Result};
 
-use super::curve::Point;
+use super::curve::{Point, ECURVE};
 use super::field::FieldElement;
-use super::signature::Signature;
+use super::signature::{RecoveryId, Signature};
 use super::{G, N};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Derivative, Clone)]
+#[derivative(Debug, PartialEq, Eq)]
 pub struct PublicKey {
     pub(crate) ec_point: Point,
+    #[derivative(PartialEq = "ignore")]
+    pub(crate) compressed: bool, // preferred SEC format
 }
 
 impl From<Point> for PublicKey {
     fn from(ec_point: Point) -> Self {
-        Self { ec_point }
+        Self {
+            ec_point,
+            compressed: true,
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for PublicKey {
+    type Error = Error;
+
+    /// Parse a public key from its SEC format bytes
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Self::deserialize(bytes)
     }
 }
 
@@ -31,7 +50,7 @@ impl PublicKey {
         let y = FieldElement::new(y);
         let ec_point = Point::new(x, y)?;
 
-        Ok(Self { ec_point })
+        Ok(Self::from(ec_point))
     }
 
     pub fn from_bytes_be<B>(x: B, y: B) -> Result<Self>
@@ -52,11 +71,20 @@ impl PublicKey {
         Self::new(x, y)
     }
 
+    /// Check that this key is a point on the curve other than the point at
+    /// infinity, keys built through `From<Point>` skip this check.
+    pub fn is_valid(&self) -> bool {
+        match &self.ec_point {
+            Point::AtInfinity => false,
+            Point::Normal(x, y) => ECURVE.contains(x, y),
+        }
+    }
+
     pub fn valid_signature<B>(&self, digest: B, signature: &Signature) -> Result<bool>
     where
         B: AsRef<[u8]>,
     {
-        signature.is_valid(digest, &self)
+        signature.is_valid(digest, self)
     }
 
     /// Serialize this public key using the SEC format
@@ -64,23 +92,89 @@ impl PublicKey {
         self.ec_point.serialize(compressed)
     }
 
-    /// Deserialize the given bytes using the SEC format
+    /// Get the hash160 of the SEC serialization of this key, as used by p2pkh
+    /// addresses and scripts
+    pub fn hash160(&self, compressed: bool) -> Result<[u8; 20]> {
+        let digest = hash160(self.serialize(compressed)?);
+        Ok(<[u8; 20]>::try_from(digest.as_slice()).unwrap()) // safe
+    }
+
+    /// Whether this key prefers the compressed SEC format, which is the case
+    /// unless it was parsed from an uncompressed one
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// Serialize this public key using its preferred SEC format
+    pub fn to_sec(&self) -> Result<Vec<u8>> {
+        self.serialize(self.compressed)
+    }
+
+    /// Deserialize the given bytes using the SEC format, remembering whether
+    /// they were compressed
     pub fn deserialize<B>(bytes: B) -> Result<Self>
     where
         B: AsRef<[u8]>,
     {
+        let bytes = bytes.as_ref();
         let ec_point = Point::deserialize(bytes)?;
-        Ok(Self { ec_point })
+        let compressed = bytes.len() == 33;
+
+        Ok(Self {
+            ec_point,
+            compressed,
+        })
     }
 
-    /// Create the address
+    /// Create the address, `testnet` selects between mainnet and testnet
     pub fn create_address(&self, compressed: bool, testnet: bool) -> Result<String> {
-        let serialized = self.serialize(compressed)?;
-        let digest = hash160(serialized);
-        let prefix = if testnet { 0x6f } else { 0x00 };
-        let data: Vec<_> = std::iter::once(prefix).chain(digest).collect();
-        Ok(base58::encode_checksum(data))
+        self.address(compressed, Network::from_testnet(testnet))
+    }
+
+    /// Create the (p2pkh) address for the given network
+    pub fn address(&self, compressed: bool, network: Network) -> Result<String> {
+        let digest = self.hash160(compressed)?;
+        p2pkh_address(&digest, network)
+    }
+
+    /// Verify a DER signature of `message`, which is hashed with `hash256`
+    pub fn verify(&self, message: &[u8], der_sig: &[u8]) -> Result<bool> {
+        let signature = Signature::deserialize(der_sig)?;
+        signature.is_valid(hash256(message), self)
+    }
+}
+
+/// Create the (p2pkh) address of some SEC-encoded public key without parsing
+/// it as a point, `compressed_is_implied` is checked against the bytes length
+pub fn address_from_sec(sec: &[u8], compressed_is_implied: bool, testnet: bool) -> Result<String> {
+    let expected_length = if compressed_is_implied { 33 } else { 65 };
+    if sec.len() != expected_length {
+        return Err(Error::InvalidSecBytesLength(sec.len()));
     }
+
+    p2pkh_address(&hash160(sec), Network::from_testnet(testnet))
+}
+
+/// Lazily derive the (p2pkh) addresses of a sequence of secrets, each one
+/// fails on its own if the secret is out of range
+pub fn addresses_for_secrets(
+    secrets: impl Iterator<Item = BigUint>,
+    compressed: bool,
+    network: Network,
+) -> impl Iterator<Item = Result<String>> {
+    secrets.map(move |secret| {
+        PrivateKey::new(secret)?
+            .public_key()
+            .address(compressed, network)
+    })
+}
+
+fn p2pkh_address(hash: &[u8], network: Network) -> Result<String> {
+    let prefix = network.params().p2pkh_prefix;
+    let data: Vec<_> = std::iter::once(prefix)
+        .chain(hash.iter().copied())
+        .collect();
+    base58::encode_checksum(data)
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -90,18 +184,34 @@ pub struct PrivateKey {
 }
 
 impl PrivateKey {
-    pub fn new<U>(secret: U) -> Self
+    /// Build a private key from its secret, which must be in the range
+    /// `[1, N)`, otherwise the public key would be degenerate
+    pub fn new<U>(secret: U) -> Result<Self>
     where
         U: Into<BigUint>,
     {
         let secret = secret.into();
+        if secret.is_zero() || secret >= *N {
+            return Err(Error::InvalidSecret);
+        }
+
         let ec_point = &*G * secret.clone();
-        let pub_key = PublicKey { ec_point };
+        let pub_key = PublicKey::from(ec_point);
 
-        Self { secret, pub_key }
+        Ok(Self { secret, pub_key })
     }
 
-    pub fn from_bytes_be<B>(secret: B) -> Self
+    /// Build a private key from exactly 32 big endian bytes, unlike
+    /// [`PrivateKey::from_bytes_be`] which takes any length
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != 32 {
+            return Err(Error::InvalidSecretLength(bytes.len()));
+        }
+
+        Self::from_bytes_be(bytes)
+    }
+
+    pub fn from_bytes_be<B>(secret: B) -> Result<Self>
     where
         B: AsRef<[u8]>,
     {
@@ -109,7 +219,7 @@ impl PrivateKey {
         Self::new(secret)
     }
 
-    pub fn from_bytes_le<B>(secret: B) -> Self
+    pub fn from_bytes_le<B>(secret: B) -> Result<Self>
     where
         B: AsRef<[u8]>,
     {
@@ -117,6 +227,41 @@ impl PrivateKey {
         Self::new(secret)
     }
 
+    /// Get the secret as 32 big endian bytes
+    pub fn to_bytes(&self) -> [u8; 32] {
+        let bytes = prepend_padding(self.secret.to_bytes_be(), 32, 0).unwrap(); // safe, secret < N
+        <[u8; 32]>::try_from(bytes.as_slice()).unwrap()
+    }
+
+    /// Parse a private key from the WIF format, either compressed or not and
+    /// for any network
+    pub fn from_wif<S>(wif: S) -> Result<Self>
+    where
+        S: AsRef<str>,
+    {
+        let data = base58::decode_checksum(wif)?;
+
+        let mainnet = Network::Mainnet.params().wif_prefix;
+        let testnet = Network::Testnet.params().wif_prefix;
+
+        match data.as_slice() {
+            [prefix, ..] if *prefix != mainnet && *prefix != testnet => {
+                Err(Error::InvalidWif("unknown prefix"))
+            }
+
+            // without the 0x01 suffix the key is meant to be uncompressed
+            [_, secret @ ..] if secret.len() == 32 => {
+                let mut private_key = Self::from_bytes_be(secret)?;
+                private_key.pub_key.compressed = false;
+                Ok(private_key)
+            }
+
+            [_, secret @ .., 0x01] if secret.len() == 32 => Self::from_bytes_be(secret),
+
+            _ => Err(Error::InvalidWif("bad length")),
+        }
+    }
+
     pub fn public_key(&self) -> &PublicKey {
         &self.pub_key
     }
@@ -130,20 +275,93 @@ impl PrivateKey {
             return Err(Error::InvalidDigestLength(digest.len()));
         }
 
-        let k = self.deterministic_k(digest)?;
-        let r = (&*G * k.clone()).x().unwrap().0.clone();
+        let k = self.deterministic_k(digest, &[])?;
+        self.create_signature_with_k(digest, &k)
+    }
 
-        let k_inv = k.modpow(&(&*N - 2usize), &*N);
+    /// Create a signature along with the recovery id needed to get the public
+    /// key back from it, see [`Signature::recover`]
+    pub fn create_recoverable_signature<B>(&self, digest: B) -> Result<(Signature, RecoveryId)>
+    where
+        B: AsRef<[u8]>,
+    {
+        let digest = digest.as_ref();
+        if digest.len() != 32 {
+            return Err(Error::InvalidDigestLength(digest.len()));
+        }
+
+        let k = self.deterministic_k(digest, &[])?;
+        self.sign_with_k(digest, &k)
+    }
+
+    /// Create a signature whose `r` has its high bit clear, so its DER
+    /// serialization doesn't need padding. The nonce is re-derived with an
+    /// increasing counter as extra data, like Bitcoin Core does.
+    pub fn create_signature_low_r<B>(&self, digest: B) -> Result<Signature>
+    where
+        B: AsRef<[u8]>,
+    {
+        let digest = digest.as_ref();
+        let mut signature = self.create_signature(digest)?;
+
+        let mut counter = 0u32;
+        while signature.r.bits() == 256 {
+            counter += 1;
+
+            let mut extra = [0u8; 32];
+            extra[..4].copy_from_slice(&counter.to_le_bytes());
+
+            let k = self.deterministic_k(digest, &extra)?;
+            signature = self.create_signature_with_k(digest, &k)?;
+        }
+
+        Ok(signature)
+    }
+
+    /// Create a signature using the given nonce `k` instead of the one derived
+    /// deterministically (RFC6979). Reusing a nonce leaks the private key.
+    pub fn create_signature_with_k<B>(&self, digest: B, k: &BigUint) -> Result<Signature>
+    where
+        B: AsRef<[u8]>,
+    {
+        let digest = digest.as_ref();
+        if digest.len() != 32 {
+            return Err(Error::InvalidDigestLength(digest.len()));
+        }
+
+        if k.is_zero() || k >= &*N {
+            return Err(Error::InvalidNonce);
+        }
+
+        Ok(self.sign_with_k(digest, k)?.0)
+    }
+
+    fn sign_with_k(&self, digest: &[u8], k: &BigUint) -> Result<(Signature, RecoveryId)> {
+        let point = G.mul_scalar(k);
+        let (x, y) = match &point {
+            Point::Normal(x, y) => (&x.0, &y.0),
+            Point::AtInfinity => return Err(Error::InvalidNonce),
+        };
+
+        let r = x.clone();
+        let mut y_is_odd = y.bit(0);
+
+        let k_inv = k.modpow(&(&*N - 2usize), &N);
         let z = BigUint::from_bytes_be(digest);
         let mut s = (z + &r * &self.secret) * k_inv % &*N;
         if s > &*N / 2usize {
+            // negating s is like signing with -k, whose point has the other y
             s = &*N - s;
+            y_is_odd = !y_is_odd;
         }
 
-        Ok(Signature::new(r, s))
+        let recovery_id = RecoveryId::from_parts(y_is_odd, x >= &*N);
+        Ok((Signature::new(r, s), recovery_id))
     }
 
-    fn deterministic_k<B>(&self, digest: B) -> Result<BigUint>
+    /// Derive the nonce following RFC6979, `extra` is appended to the key
+    /// material (section 3.6)
+    fn deterministic_k<B>(&self, digest: B, extra: &[u8]) -> Result<BigUint>
     where
         B: AsRef<[u8]>,
     {
@@ -168,6 +386,7 @@ impl PrivateKey {
             .chain(&[0x00])
             .chain(&secret_bytes)
             .chain(digest)
+            .chain(extra)
             .finalize()
             .into_bytes();
 
@@ -180,6 +399,7 @@ impl PrivateKey {
             .chain(&[0x01])
             .chain(&secret_bytes)
             .chain(digest)
+            .chain(extra)
             .finalize()
             .into_bytes();
 
@@ -203,14 +423,96 @@ impl PrivateKey {
         }
     }
 
+    /// Create the WIF, `testnet` selects between mainnet and testnet
     pub fn create_wif(&self, compressed: bool, testnet: bool) -> Result<String> {
+        self.wif(compressed, Network::from_testnet(testnet))
+    }
+
+    /// Create the WIF for the given network
+    pub fn wif(&self, compressed: bool, network: Network) -> Result<String> {
         let secret_bytes = prepend_padding(self.secret.to_bytes_be(), 32, 0)?;
-        let prefix = if testnet { 0xef } else { 0x80 };
+        let prefix = network.params().wif_prefix;
         let mut data: Vec<_> = std::iter::once(prefix).chain(secret_bytes).collect();
         if compressed {
             data.push(0x01)
         }
 
-        Ok(base58::encode_checksum(data))
+        base58::encode_checksum(data)
+    }
+}
+
+/// A private key along with its public key, bundling the usual key operations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyPair {
+    private_key: PrivateKey,
+}
+
+impl From<PrivateKey> for KeyPair {
+    fn from(private_key: PrivateKey) -> Self {
+        Self { private_key }
+    }
+}
+
+impl KeyPair {
+    pub fn new<U>(secret: U) -> Result<Self>
+    where
+        U: Into<BigUint>,
+    {
+        Ok(Self::from(PrivateKey::new(secret)?))
+    }
+
+    pub fn from_wif<S>(wif: S) -> Result<Self>
+    where
+        S: AsRef<str>,
+    {
+        Ok(Self::from(PrivateKey::from_wif(wif)?))
+    }
+
+    pub fn private_key(&self) -> &PrivateKey {
+        &self.private_key
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        self.private_key.public_key()
+    }
+
+    /// Whether the key uses the compressed SEC format, taken from the WIF
+    /// when parsed from one and `true` otherwise
+    pub fn is_compressed(&self) -> bool {
+        self.public_key().is_compressed()
+    }
+
+    /// Override the preferred SEC format
+    pub fn with_compressed(mut self, compressed: bool) -> Self {
+        self.private_key.pub_key.compressed = compressed;
+        self
+    }
+
+    /// Create the (p2pkh) address for the given network, compressed or not
+    /// according to [`KeyPair::is_compressed`]
+    pub fn preferred_address(&self, network: Network) -> Result<String> {
+        self.address(self.is_compressed(), network)
+    }
+
+    /// Create the WIF for the given network, see [`KeyPair::preferred_address`]
+    pub fn preferred_wif(&self, network: Network) -> Result<String> {
+        self.wif(self.is_compressed(), network)
+    }
+
+    /// Create the (p2pkh) address for the given network
+    pub fn address(&self, compressed: bool, network: Network) -> Result<String> {
+        self.public_key().address(compressed, network)
+    }
+
+    /// Create the WIF for the given network
+    pub fn wif(&self, compressed: bool, network: Network) -> Result<String> {
+        self.private_key.wif(compressed, network)
+    }
+
+    pub fn sign<B>(&self, digest: B) -> Result<Signature>
+    where
+        B: AsRef<[u8]>,
+    {
+        self.private_key.create_signature(digest)
     }
 }
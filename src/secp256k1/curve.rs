@@ -3,13 +3,13 @@ use std::ops::{Add, Mul};
 use lazy_static::lazy_static;
 use num_bigint::BigUint;
 use num_integer::Integer;
-use num_traits::{One, Pow, Zero};
+use num_traits::{Pow, Zero};
 
 use crate::utils::prepend_padding;
 use crate::{Error, Result};
 
 use super::field::FieldElement;
-use super::field::PRIME;
+use super::N;
 
 lazy_static! {
     pub(crate) static ref B: FieldElement = FieldElement::new(7usize);
@@ -31,6 +31,15 @@ impl EllipticCurve {
     pub fn contains(&self, x: &FieldElement, y: &FieldElement) -> bool {
         y.pow(2usize) == x.pow(3usize) + &self.a * x + &self.b
     }
+
+    /// Build a point checking it's on this curve
+    pub fn point(&self, x: FieldElement, y: FieldElement) -> Result<Point> {
+        if self.contains(&x, &y) {
+            Ok(Point::Normal(x, y))
+        } else {
+            Err(Error::PointNotOnTheCurve)
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -41,11 +50,7 @@ pub enum Point {
 
 impl Point {
     pub fn new(x: FieldElement, y: FieldElement) -> Result<Self> {
-        if ECURVE.contains(&x, &y) {
-            Ok(Self::Normal(x, y))
-        } else {
-            Err(Error::PointNotOnTheCurve)
-        }
+        ECURVE.point(x, y)
     }
 
     pub fn at_infinity() -> Self {
@@ -54,14 +59,14 @@ impl Point {
 
     pub fn x(&self) -> Option<&FieldElement> {
         match self {
-            Point::AtInfinity { .. } => None,
+            Point::AtInfinity => None,
             Point::Normal(x, _) => Some(x),
         }
     }
 
     pub fn y(&self) -> Option<&FieldElement> {
         match self {
-            Point::AtInfinity { .. } => None,
+            Point::AtInfinity => None,
             Point::Normal(_, y) => Some(y),
         }
     }
@@ -70,6 +75,33 @@ impl Point {
         matches!(self, Self::AtInfinity)
     }
 
+    /// Add `rhs` to this point, checking that the result is on the curve,
+    /// which catches corrupted (off-curve) inputs
+    pub fn checked_add(&self, rhs: &Point) -> Result<Point> {
+        match self + rhs {
+            Point::Normal(x, y) if !ECURVE.contains(&x, &y) => Err(Error::PointNotOnTheCurve),
+            result => Ok(result),
+        }
+    }
+
+    /// Multiply this point by the scalar `k`, the scalar is reduced modulo the
+    /// group order `N` (not the field prime). Bits are taken from the most
+    /// significant one, so this point is only borrowed instead of cloned and
+    /// doubled along the way.
+    pub fn mul_scalar(&self, k: &BigUint) -> Self {
+        let coef = k % &*N;
+
+        let mut result = Point::zero();
+        for bit in (0..coef.bits()).rev() {
+            result = &result + &result;
+            if coef.bit(bit) {
+                result = &result + self;
+            }
+        }
+
+        result
+    }
+
     /// Serialize the given point with the SEC format
     pub fn serialize(&self, compressed: bool) -> Result<Vec<u8>> {
         match self {
@@ -105,35 +137,50 @@ impl Point {
     {
         let bytes = bytes.as_ref();
 
-        let length = bytes.len();
-        if length != 33 && length != 65 {
-            return Err(Error::InvalidSecBytesLength(length));
-        }
+        match (bytes.first(), bytes.len()) {
+            // uncompressed sec format, or hybrid (which also tells y's parity)
+            (Some(prefix @ (0x04 | 0x06 | 0x07)), 65) => {
+                let x = FieldElement::new(BigUint::from_bytes_be(&bytes[1..33]));
+                let y = FieldElement::new(BigUint::from_bytes_be(&bytes[33..65]));
 
-        // uncompressed sec format
-        if bytes[0] == 0x04 {
-            let x = FieldElement::new(BigUint::from_bytes_be(&bytes[1..33]));
-            let y = FieldElement::new(BigUint::from_bytes_be(&bytes[33..65]));
-            return Self::new(x, y);
-        }
+                if *prefix != 0x04 && y.0.is_odd() != (*prefix == 0x07) {
+                    return Err(Error::InvalidSecPrefix(*prefix));
+                }
+
+                Self::new(x, y)
+            }
+
+            // compressed sec format
+            (Some(prefix @ (0x02 | 0x03)), 33) => {
+                let x = FieldElement::new(BigUint::from_bytes_be(&bytes[1..]));
+                let (even, odd) = Self::from_x(x)?;
+
+                Ok(if *prefix == 0x02 { even } else { odd })
+            }
 
-        // compressed sec format
-        let y_is_even = bytes[0] == 0x02;
-        let x = FieldElement::new(BigUint::from_bytes_be(&bytes[1..]));
+            (Some(prefix), 33 | 65) => Err(Error::InvalidSecPrefix(*prefix)),
+            (_, length) => Err(Error::InvalidSecBytesLength(length)),
+        }
+    }
 
+    /// Get both points with the given `x` coordinate, the one with an even `y`
+    /// first, failing if there's none on the curve
+    pub fn from_x(x: FieldElement) -> Result<(Self, Self)> {
         // elliptic curve equation: y^2 = x^3 + x*a + b
         // rhs of the elliptic curve equation (note a = 0)
         let alpha = x.pow(3u8) + &*B;
 
         // solve lhs
-        let beta = alpha.sqrt();
+        let beta = alpha.checked_sqrt().ok_or(Error::PointNotOnTheCurve)?;
+        let other = beta.add_inv();
 
-        let y = match (beta.0.is_even(), y_is_even) {
-            (true, true) | (false, false) => beta,
-            (true, false) | (false, true) => FieldElement::new(&*PRIME - beta.0),
+        let (even, odd) = if beta.0.is_even() {
+            (beta, other)
+        } else {
+            (other, beta)
         };
 
-        Ok(Self::Normal(x, y)) // no need to check
+        Ok((Self::Normal(x.clone(), even), Self::Normal(x, odd)))
     }
 }
 
@@ -147,7 +194,7 @@ impl Zero for Point {
     }
 }
 
-impl<'a, 'b> Add<&'a Point> for &'b Point {
+impl<'a> Add<&'a Point> for &Point {
     type Output = Point;
 
     fn add(self, rhs: &'a Point) -> Self::Output {
@@ -186,29 +233,14 @@ impl<'a, 'b> Add<&'a Point> for &'b Point {
     }
 }
 
-impl<'a, U> Mul<U> for &'a Point
+impl<U> Mul<U> for &Point
 where
     U: Into<BigUint>,
 {
     type Output = Point;
 
     fn mul(self, coef: U) -> Self::Output {
-        let mut coef = coef.into() % &*PRIME;
-
-        let one = BigUint::one();
-        let mut result = Point::zero();
-        let mut current = self.clone();
-
-        while !coef.is_zero() {
-            if &coef & &one == one {
-                result = &result + &current;
-            }
-
-            coef >>= 1;
-            current = &current + &current;
-        }
-
-        result
+        self.mul_scalar(&coef.into())
     }
 }
 
@@ -224,3 +256,128 @@ where
 }
 
 forward_binop_impl!(for non-copyable Point where Add does add);
+
+#[cfg(test)]
+mod tests {
+    use num_traits::One;
+
+    use super::*;
+    use crate::secp256k1::G;
+
+    #[test]
+    fn point_through_curve() {
+        let x = G.x().unwrap().clone();
+        let y = G.y().unwrap().clone();
+
+        let point = ECURVE.point(x.clone(), y).unwrap();
+        assert_eq!(point, *G);
+
+        let result = ECURVE.point(x, FieldElement::new(1usize));
+        assert!(matches!(result, Err(Error::PointNotOnTheCurve)));
+    }
+
+    #[test]
+    fn checked_addition() -> Result<()> {
+        let double = G.checked_add(&G)?;
+        assert_eq!(double, &*G * 2usize);
+        assert_eq!(double.checked_add(&G)?, &*G * 3usize);
+
+        let minus_g = &*G * (&*N - 1usize);
+        assert!(G.checked_add(&minus_g)?.is_point_at_inf());
+        assert_eq!(Point::at_infinity().checked_add(&G)?, *G);
+
+        let x = G.x().unwrap().clone();
+        let y = G.y().unwrap() + &FieldElement::new(1usize);
+        let corrupted = Point::Normal(x, y);
+        assert!(corrupted.checked_add(&double).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn both_points_from_x() -> Result<()> {
+        let (even, odd) = Point::from_x(G.x().unwrap().clone())?;
+
+        assert!(even.y().unwrap().0.is_even());
+        assert!(odd.y().unwrap().0.is_odd());
+        assert!(even == *G || odd == *G);
+        assert!((&even + &odd).is_point_at_inf());
+        assert_eq!(odd, &even * (&*N - 1usize));
+
+        // 5^3 + 7 isn't a quadratic residue
+        let result = Point::from_x(FieldElement::new(5usize));
+        assert!(matches!(result, Err(Error::PointNotOnTheCurve)));
+
+        let mut compressed = vec![0x02];
+        compressed.extend(prepend_padding(vec![5], 32, 0)?);
+        assert!(Point::deserialize(compressed).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn hybrid_sec_format() -> Result<()> {
+        let mut hybrid = G.serialize(false)?;
+        hybrid[0] = 0x06; // y of G is even
+        assert_eq!(Point::deserialize(&hybrid)?, *G);
+
+        hybrid[0] = 0x07;
+        let result = Point::deserialize(&hybrid);
+        assert!(matches!(result, Err(Error::InvalidSecPrefix(0x07))));
+
+        let minus_g = &*G * (&*N - 1usize);
+        let mut hybrid = minus_g.serialize(false)?;
+        hybrid[0] = 0x07;
+        assert_eq!(Point::deserialize(&hybrid)?, minus_g);
+
+        // hybrid prefixes are only valid for 65 bytes
+        let mut compressed = G.serialize(true)?;
+        compressed[0] = 0x06;
+        assert!(Point::deserialize(&compressed).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn borrowing_mul_scalar() {
+        // the previous least significant bit first implementation
+        let reference = |point: &Point, k: &BigUint| {
+            let mut coef = k % &*N;
+            let mut result = Point::zero();
+            let mut current = point.clone();
+
+            while !coef.is_zero() {
+                if coef.bit(0) {
+                    result = &result + &current;
+                }
+
+                coef >>= 1;
+                current = &current + &current;
+            }
+
+            result
+        };
+
+        let scalars = vec![
+            BigUint::zero(),
+            BigUint::one(),
+            BigUint::from(2usize),
+            BigUint::from(8675309usize),
+            biguint!("8f8a276c19f4149656b280621e358cce24f5f52542772691ee69063b74f15d15"),
+            &*N - 1usize,
+            N.clone(),
+            &*N + 5usize,
+        ];
+
+        let double = &*G * 2usize;
+        for k in &scalars {
+            assert_eq!(G.mul_scalar(k), reference(&G, k));
+            assert_eq!(double.mul_scalar(k), reference(&double, k));
+            assert_eq!(G.mul_scalar(k), &*G * k.clone());
+        }
+
+        assert!(Point::at_infinity()
+            .mul_scalar(&BigUint::one())
+            .is_point_at_inf());
+    }
+}
@@ -3,7 +3,9 @@ use std::ops::{Add, Div, Mul, Sub};
 use lazy_static::lazy_static;
 use num_bigint::{BigInt, BigUint, Sign};
 use num_integer::Integer;
-use num_traits::{One, Pow, Zero};
+use num_traits::{One, Pow, ToPrimitive, Zero};
+
+use crate::{Error, Result};
 
 lazy_static! {
     /// `secp256k1` prime = 2^256 - 2^32 - 977
@@ -11,11 +13,16 @@ lazy_static! {
         biguint!("fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f");
 }
 
+/// Largest exponent computed by repeated multiplication instead of `modpow`
+const SMALL_EXPONENT: u8 = 4;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FieldElement(pub(crate) BigUint);
 
 impl FieldElement {
-    /// Build a new element in the S256 field
+    /// Build a new element in the S256 field, `number` is silently reduced
+    /// mod `PRIME` (e.g. `PRIME + 5` and `5` build the same element), use
+    /// [`FieldElement::try_new`] to reject unreduced numbers instead.
     pub fn new<U>(number: U) -> Self
     where
         U: Into<BigUint>,
@@ -23,10 +30,32 @@ impl FieldElement {
         Self(number.into() % &*PRIME)
     }
 
+    /// Build a new element in the S256 field, failing if `number` isn't
+    /// already reduced mod `PRIME`
+    pub fn try_new<U>(number: U) -> Result<Self>
+    where
+        U: Into<BigUint>,
+    {
+        let number = number.into();
+
+        if Self::is_on_field(&number) {
+            Ok(Self(number))
+        } else {
+            Err(Error::NotInField)
+        }
+    }
+
+    /// Check whether `number` is a reduced element of the field, i.e. is
+    /// lower than `PRIME`
+    #[inline]
+    pub fn is_on_field(number: &BigUint) -> bool {
+        number < &*PRIME
+    }
+
     /// Get the _additive inverse_ of this element.
     #[inline]
     pub fn add_inv(&self) -> Self {
-        Self(&*PRIME - &self.0)
+        Self::new(&*PRIME - &self.0)
     }
 
     /// Get the _multiplicative inverse_ of this element.
@@ -36,11 +65,22 @@ impl FieldElement {
         self.pow(&*PRIME - 2usize)
     }
 
-    /// Get the square root of this element
+    /// Get the square root of this element, the result is only meaningful if
+    /// this element is a quadratic residue (see [`FieldElement::checked_sqrt`])
     #[inline]
     pub fn sqrt(&self) -> Self {
         self.pow((&*PRIME + 1usize) / 4usize)
     }
+
+    /// Get the square root of this element, or `None` if it doesn't have one
+    pub fn checked_sqrt(&self) -> Option<Self> {
+        let root = self.sqrt();
+        if &root * &root == *self {
+            Some(root)
+        } else {
+            None
+        }
+    }
 }
 
 impl Zero for FieldElement {
@@ -59,7 +99,7 @@ impl One for FieldElement {
     }
 }
 
-impl<'a, E> Pow<E> for &'a FieldElement
+impl<E> Pow<E> for &FieldElement
 where
     E: Into<BigInt>,
 {
@@ -75,12 +115,18 @@ where
             }
         };
 
-        let number = self.0.modpow(&exponent, &*PRIME);
+        // small exponents (squares and cubes in point addition) are cheaper
+        // to compute with plain multiplications
+        if let Some(exp) = exponent.to_u8().filter(|exp| *exp <= SMALL_EXPONENT) {
+            return (0..exp).fold(FieldElement::one(), |acc, _| &acc * self);
+        }
+
+        let number = self.0.modpow(&exponent, &PRIME);
         FieldElement(number)
     }
 }
 
-impl<'a, 'b> Add<&'a FieldElement> for &'b FieldElement {
+impl<'a> Add<&'a FieldElement> for &FieldElement {
     type Output = FieldElement;
 
     fn add(self, rhs: &'a FieldElement) -> Self::Output {
@@ -89,7 +135,7 @@ impl<'a, 'b> Add<&'a FieldElement> for &'b FieldElement {
     }
 }
 
-impl<'a, 'b> Sub<&'a FieldElement> for &'b FieldElement {
+impl<'a> Sub<&'a FieldElement> for &FieldElement {
     type Output = FieldElement;
 
     fn sub(self, rhs: &'a FieldElement) -> Self::Output {
@@ -97,7 +143,7 @@ impl<'a, 'b> Sub<&'a FieldElement> for &'b FieldElement {
     }
 }
 
-impl<'a, 'b> Mul<&'a FieldElement> for &'b FieldElement {
+impl<'a> Mul<&'a FieldElement> for &FieldElement {
     type Output = FieldElement;
 
     fn mul(self, rhs: &'a FieldElement) -> Self::Output {
@@ -106,7 +152,7 @@ impl<'a, 'b> Mul<&'a FieldElement> for &'b FieldElement {
     }
 }
 
-impl<'a, 'b> Div<&'a FieldElement> for &'b FieldElement {
+impl<'a> Div<&'a FieldElement> for &FieldElement {
     type Output = FieldElement;
 
     fn div(self, rhs: &'a FieldElement) -> Self::Output {
@@ -114,7 +160,7 @@ impl<'a, 'b> Div<&'a FieldElement> for &'b FieldElement {
     }
 }
 
-impl<'a> Mul<usize> for &'a FieldElement {
+impl Mul<usize> for &FieldElement {
     type Output = FieldElement;
 
     fn mul(self, rhs: usize) -> Self::Output {
@@ -134,3 +180,57 @@ forward_binop_impl!(for non-copyable FieldElement where Add does add);
 forward_binop_impl!(for non-copyable FieldElement where Sub does sub);
 forward_binop_impl!(for non-copyable FieldElement where Mul does mul);
 forward_binop_impl!(for non-copyable FieldElement where Div does div);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reduction_on_new() {
+        let unreduced = &*PRIME + 5usize;
+
+        assert!(!FieldElement::is_on_field(&unreduced));
+        assert!(FieldElement::is_on_field(&BigUint::from(5usize)));
+        assert_eq!(
+            FieldElement::new(unreduced.clone()),
+            FieldElement::new(5usize)
+        );
+        assert!(FieldElement::try_new(unreduced).is_err());
+        assert!(FieldElement::try_new(PRIME.clone()).is_err());
+        assert_eq!(
+            FieldElement::try_new(5usize).unwrap(),
+            FieldElement::new(5usize)
+        );
+    }
+
+    #[test]
+    fn zero_additive_inverse() {
+        let inv = FieldElement::zero().add_inv();
+        assert!(inv.is_zero());
+    }
+
+    #[test]
+    fn checked_sqrt() {
+        let four = FieldElement::new(4usize);
+        let root = four.checked_sqrt().unwrap();
+        assert_eq!(&root * &root, four);
+
+        // -1 isn't a quadratic residue since PRIME = 3 mod 4
+        let minus_one = FieldElement::one().add_inv();
+        assert_eq!(minus_one.checked_sqrt(), None);
+    }
+
+    #[test]
+    fn small_exponents_fast_path() {
+        let element = FieldElement::new(biguint!(
+            "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"
+        ));
+
+        for exp in 0..=5u32 {
+            let expected = element.0.modpow(&BigUint::from(exp), &PRIME);
+            assert_eq!(element.pow(exp).0, expected, "exponent {}", exp);
+        }
+
+        assert_eq!(FieldElement::zero().pow(0u32), FieldElement::one());
+    }
+}
@@ -5,6 +5,9 @@ pub mod curve;
 pub mod field;
 pub mod signature;
 
+#[cfg(feature = "serde")]
+mod serde_impls;
+
 use curve::Point;
 use num_bigint::BigUint;
 
@@ -17,6 +20,21 @@ lazy_static! {
         biguint!("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141");
 }
 
+/// Get the generator point `G` of the `secp256k1` group
+pub fn generator() -> &'static Point {
+    &G
+}
+
+/// Get the order `N` of the `secp256k1` group
+pub fn order() -> &'static BigUint {
+    &N
+}
+
+/// Reduce a scalar modulo the group order
+pub fn reduce_scalar(k: &BigUint) -> BigUint {
+    k % &*N
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -25,5 +43,23 @@ mod tests {
     fn order_n() {
         let res = &*G * N.clone();
         assert!(res.is_point_at_inf());
+
+        let res = &*G * (&*N - 1usize) + &*G;
+        assert!(res.is_point_at_inf());
+    }
+
+    #[test]
+    fn reduce_scalars() {
+        assert_eq!(reduce_scalar(order()), BigUint::from(0usize));
+        assert_eq!(reduce_scalar(&(&*N + 5usize)), BigUint::from(5usize));
+        assert_eq!(reduce_scalar(&BigUint::from(5usize)), BigUint::from(5usize));
+    }
+
+    #[test]
+    fn scalar_reduced_mod_n() {
+        // N < N + 5 < PRIME, so reducing modulo PRIME would give a wrong point
+        let k = &*N + 5usize;
+        assert_eq!(G.mul_scalar(&k), G.mul_scalar(&BigUint::from(5usize)));
+        assert_eq!(&*G * k, &*G * 5usize);
     }
 }
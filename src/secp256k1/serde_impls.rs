@@ -0,0 +1,56 @@
+//! Serialize the crypto types as their canonical hex/string forms: DER for
+//! signatures, SEC for public keys (in their preferred format) and mainnet
+//! WIF for private keys (compressed unless parsed from an uncompressed one).
+
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::crypto::{PrivateKey, PublicKey};
+use super::signature::Signature;
+
+impl Serialize for Signature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let der = Signature::serialize(self).map_err(S::Error::custom)?;
+        serializer.serialize_str(&hex::encode(der))
+    }
+}
+
+impl<'de> Deserialize<'de> for Signature {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let der = String::deserialize(deserializer)?;
+        let der = hex::decode(der).map_err(D::Error::custom)?;
+        Signature::deserialize(der.as_slice()).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for PublicKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let sec = self.to_sec().map_err(S::Error::custom)?;
+        serializer.serialize_str(&hex::encode(sec))
+    }
+}
+
+impl<'de> Deserialize<'de> for PublicKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let sec = String::deserialize(deserializer)?;
+        let sec = hex::decode(sec).map_err(D::Error::custom)?;
+        PublicKey::deserialize(sec).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for PrivateKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let wif = self
+            .create_wif(self.pub_key.compressed, false)
+            .map_err(S::Error::custom)?;
+        serializer.serialize_str(&wif)
+    }
+}
+
+impl<'de> Deserialize<'de> for PrivateKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let wif = String::deserialize(deserializer)?;
+        PrivateKey::from_wif(wif).map_err(D::Error::custom)
+    }
+}
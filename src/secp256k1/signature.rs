@@ -2,13 +2,47 @@ use std::io::Read;
 
 use bytes::Buf;
 use num_bigint::BigUint;
+use num_traits::Zero;
 
-use crate::utils::strip_start;
+use crate::utils::{prepend_padding, strip_start};
 use crate::{Error, Result};
 
 use super::crypto::PublicKey;
+use super::curve::Point;
+use super::field::FieldElement;
 use super::{G, N};
 
+/// Recovery id of a signature (0 to 3), telling which point `R` it was made
+/// with: the parity of its `y` and whether its `x` overflowed `N`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RecoveryId(u8);
+
+impl RecoveryId {
+    pub fn new(id: u8) -> Result<Self> {
+        if id <= 3 {
+            Ok(Self(id))
+        } else {
+            Err(Error::InvalidSignature("recovery id out of range"))
+        }
+    }
+
+    pub fn from_parts(y_is_odd: bool, x_overflow: bool) -> Self {
+        Self((x_overflow as u8) << 1 | y_is_odd as u8)
+    }
+
+    pub fn y_is_odd(self) -> bool {
+        self.0 & 0x01 != 0
+    }
+
+    pub fn x_overflow(self) -> bool {
+        self.0 & 0x02 != 0
+    }
+
+    pub fn to_u8(self) -> u8 {
+        self.0
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Signature {
     pub(crate) r: BigUint,
@@ -25,6 +59,44 @@ impl Signature {
         Self { r, s }
     }
 
+    /// Build a signature from `r` and `s` as big endian bytes, both must be in
+    /// the range `[1, N)`
+    pub fn from_bytes_be<B>(r: B, s: B) -> Result<Self>
+    where
+        B: AsRef<[u8]>,
+    {
+        let r = BigUint::from_bytes_be(r.as_ref());
+        let s = BigUint::from_bytes_be(s.as_ref());
+        Self::checked(r, s)
+    }
+
+    /// Same as [`Signature::from_bytes_be`] but with little endian bytes
+    pub fn from_bytes_le<B>(r: B, s: B) -> Result<Self>
+    where
+        B: AsRef<[u8]>,
+    {
+        let r = BigUint::from_bytes_le(r.as_ref());
+        let s = BigUint::from_bytes_le(s.as_ref());
+        Self::checked(r, s)
+    }
+
+    fn checked(r: BigUint, s: BigUint) -> Result<Self> {
+        let in_range = |x: &BigUint| !x.is_zero() && x < &*N;
+        if in_range(&r) && in_range(&s) {
+            Ok(Self { r, s })
+        } else {
+            Err(Error::InvalidSignature("r or s out of range"))
+        }
+    }
+
+    pub fn r(&self) -> &BigUint {
+        &self.r
+    }
+
+    pub fn s(&self) -> &BigUint {
+        &self.s
+    }
+
     pub fn is_valid<B>(&self, digest: B, pub_key: &PublicKey) -> Result<bool>
     where
         B: AsRef<[u8]>,
@@ -35,20 +107,73 @@ impl Signature {
         }
 
         let z = BigUint::from_bytes_be(digest);
-        let s_inv = self.s.modpow(&(&*N - 2usize), &*N);
+        let s_inv = self.s.modpow(&(&*N - 2usize), &N);
 
         let u = (&z * &s_inv) % &*N;
         let v = (&self.r * &s_inv) % &*N;
 
+        // crafted signatures could lead to the point at infinity, which
+        // doesn't have an x coordinate
         let total = &*G * u + &pub_key.ec_point * v;
-        Ok(total.x().unwrap().0 == self.r)
+        match total.x() {
+            Some(x) => Ok(x.0 == self.r),
+            None => Ok(false),
+        }
     }
 
-    /// Serialize signature with DER format
+    /// Recover the public key that created this signature over `digest`
+    pub fn recover<B>(&self, digest: B, recovery_id: RecoveryId) -> Result<PublicKey>
+    where
+        B: AsRef<[u8]>,
+    {
+        let digest = digest.as_ref();
+        if digest.len() != 32 {
+            return Err(Error::InvalidDigestLength(digest.len()));
+        }
+
+        if self.r.is_zero() || self.s.is_zero() || self.s >= *N {
+            return Err(Error::InvalidSignature("r or s out of range"));
+        }
+
+        // r may already be the full x coordinate, as created by this crate
+        let x = if recovery_id.x_overflow() && self.r < *N {
+            &self.r + &*N
+        } else {
+            self.r.clone()
+        };
+
+        let x = FieldElement::try_new(x)?;
+        let (even, odd) = Point::from_x(x)?;
+        let point = if recovery_id.y_is_odd() { odd } else { even };
+
+        let r = &self.r % &*N;
+        let r_inv = r.modpow(&(&*N - 2usize), &N);
+        let z = BigUint::from_bytes_be(digest) % &*N;
+
+        // Q = r^-1 * (s * R - z * G)
+        let minus_z = (&*N - z) % &*N;
+        let total = point.mul_scalar(&self.s) + &*G * minus_z;
+        let pub_key = total * r_inv;
+
+        if pub_key.is_point_at_inf() {
+            return Err(Error::InvalidSignature("recovered the point at infinity"));
+        }
+
+        Ok(PublicKey::from(pub_key))
+    }
+
+    /// Check whether both signatures were created with the same nonce `k`,
+    /// which leaks the private key if they're also from the same key
+    pub fn shares_nonce(&self, other: &Signature) -> bool {
+        self.r == other.r
+    }
+
+    /// Serialize signature with DER format, a zero `r` or `s` (which is never
+    /// valid) is encoded as a single zero byte
     pub fn serialize(&self) -> Result<Vec<u8>> {
         let r_bigendian = self.r.to_bytes_be();
         let r_bigendian = strip_start(&r_bigendian, 0x00);
-        let r_bigendian = if r_bigendian[0] & 0x80 == 0x80 {
+        let r_bigendian = if r_bigendian.first().is_none_or(|b| b & 0x80 == 0x80) {
             std::iter::once(0x00u8)
                 .chain(r_bigendian.iter().copied())
                 .collect::<Vec<_>>()
@@ -61,7 +186,7 @@ impl Signature {
 
         let s_bigendian = self.s.to_bytes_be();
         let s_bigendian = strip_start(&s_bigendian, 0x00);
-        let s_bigendian = if s_bigendian[0] & 0x80 == 0x80 {
+        let s_bigendian = if s_bigendian.first().is_none_or(|b| b & 0x80 == 0x80) {
             std::iter::once(0x00u8)
                 .chain(s_bigendian.iter().copied())
                 .collect::<Vec<_>>()
@@ -83,6 +208,50 @@ impl Signature {
         Ok(serialized)
     }
 
+    /// Serialize signature in the compact format, `r` and `s` as 32 bytes
+    /// big endian each
+    pub fn to_compact(&self) -> Result<[u8; 64]> {
+        let r_bigendian = prepend_padding(self.r.to_bytes_be(), 32, 0u8)?;
+        let s_bigendian = prepend_padding(self.s.to_bytes_be(), 32, 0u8)?;
+
+        let mut compact = [0u8; 64];
+        compact[..32].copy_from_slice(&r_bigendian);
+        compact[32..].copy_from_slice(&s_bigendian);
+
+        Ok(compact)
+    }
+
+    /// Serialize signature with DER format followed by the `sighash` type byte,
+    /// as found in script sigs
+    pub fn serialize_with_sighash(&self, sighash: u8) -> Result<Vec<u8>> {
+        let mut serialized = self.serialize()?;
+        serialized.push(sighash);
+        Ok(serialized)
+    }
+
+    /// Parse a DER signature followed by its sighash type byte, which is
+    /// required (see [`Signature::from_der`] otherwise)
+    pub fn deserialize_with_sighash(bytes: &[u8]) -> Result<(Self, u8)> {
+        match Self::from_der(bytes)? {
+            (signature, Some(sighash)) => Ok((signature, sighash)),
+            (_, None) => Err(Error::InvalidSignature("missing sighash")),
+        }
+    }
+
+    /// Parse a DER signature from a slice, such as a script element. If the DER
+    /// encoding is followed by a SIGHASH type byte, it's stripped and returned
+    /// as well.
+    pub fn from_der(bytes: &[u8]) -> Result<(Self, Option<u8>)> {
+        match bytes {
+            [_, claimed, .., sighash] if *claimed as usize + 3 == bytes.len() => {
+                let signature = Self::deserialize(&bytes[..bytes.len() - 1])?;
+                Ok((signature, Some(*sighash)))
+            }
+
+            _ => Ok((Self::deserialize(bytes)?, None)),
+        }
+    }
+
     pub fn deserialize(bytes: impl Buf) -> Result<Self> {
         let size = bytes.remaining();
         let mut reader = bytes.reader();
@@ -94,7 +263,7 @@ impl Signature {
             return Err(Error::InvalidSignature("bad compound"));
         }
 
-        let claimed_size = (buf[1] + 2) as usize;
+        let claimed_size = buf[1] as usize + 2;
         if claimed_size != size {
             return Err(Error::InvalidSignature("bad signature size"));
         }
@@ -124,15 +293,79 @@ impl Signature {
             return Err(Error::InvalidSignature("signature too long"));
         }
 
+        if r.is_zero() || s.is_zero() {
+            return Err(Error::InvalidSignature("zero r or s"));
+        }
+
         Ok(Self { r, s })
     }
 }
 
+/// Recover the private key from two signatures over different digests (`z1`
+/// and `z2`) that reused the same nonce, `None` if that's not the case
+pub fn recover_private_key<B>(sig1: &Signature, z1: B, sig2: &Signature, z2: B) -> Option<BigUint>
+where
+    B: AsRef<[u8]>,
+{
+    if !sig1.shares_nonce(sig2) {
+        return None;
+    }
+
+    let n = &*N;
+    let inverse = |x: &BigUint| x.modpow(&(n - 2usize), n);
+
+    let z1 = BigUint::from_bytes_be(z1.as_ref()) % n;
+    let z2 = BigUint::from_bytes_be(z2.as_ref()) % n;
+    let z_diff = (&z1 + n - &z2) % n;
+    let r_inv = inverse(&sig1.r);
+
+    // low-s normalization may have negated one of the s values, so try both
+    // and keep the nonce that actually gives r
+    let s2_candidates = [sig2.s.clone(), (n - &sig2.s) % n];
+    s2_candidates.iter().find_map(|s2| {
+        let s_diff = (&sig1.s + n - s2) % n;
+        if s_diff.is_zero() {
+            return None;
+        }
+
+        let k = (&z_diff * inverse(&s_diff)) % n;
+        let secret = (((&sig1.s * &k) % n + n - &z1) * &r_inv) % n;
+
+        match (&*G * k).x() {
+            Some(x) if x.0 == sig1.r && !secret.is_zero() => Some(secret),
+            _ => None,
+        }
+    })
+}
+
+/// Verify a batch of independent signatures, each item being the signature,
+/// the digest and the public key. With the `parallel` feature the items are
+/// verified in parallel.
+pub fn verify_batch(items: &[(Signature, [u8; 32], PublicKey)]) -> Vec<bool> {
+    let verify = |(signature, digest, pub_key): &(Signature, [u8; 32], PublicKey)| {
+        signature.is_valid(digest, pub_key).unwrap_or(false)
+    };
+
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        items.par_iter().map(verify).collect()
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    {
+        items.iter().map(verify).collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use hex_literal::hex;
 
     use super::Signature;
+    use super::N;
+    use crate::utils::prepend_padding;
+    use crate::Error;
 
     #[test]
     fn der_format() {
@@ -151,4 +384,109 @@ mod tests {
         let deserialized = Signature::deserialize(serialized.as_slice()).unwrap();
         assert_eq!(deserialized, signature);
     }
+
+    #[test]
+    fn from_der_with_sighash() {
+        // signature taken from a p2pkh script sig, SIGHASH_ALL appended
+        let bytes = hex!(
+            "3045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a98
+            6d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01"
+        );
+
+        let r = biguint!("ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f");
+        let s = biguint!("7a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed");
+        let expected = Signature::new(r, s);
+
+        let (signature, sighash) = Signature::from_der(&bytes).unwrap();
+        assert_eq!(signature, expected);
+        assert_eq!(sighash, Some(0x01));
+
+        let (signature, sighash) = Signature::from_der(&bytes[..bytes.len() - 1]).unwrap();
+        assert_eq!(signature, expected);
+        assert_eq!(sighash, None);
+    }
+
+    #[test]
+    fn large_claimed_lengths() {
+        // the claimed length plus the two header bytes doesn't fit in a byte
+        for claimed in [0xfe, 0xff] {
+            let bytes = [0x30, claimed, 0x02, 0x00, 0x00];
+            assert!(Signature::from_der(&bytes).is_err());
+            assert!(Signature::deserialize(&bytes[..]).is_err());
+            assert!(Signature::deserialize(&bytes[..4]).is_err());
+        }
+    }
+
+    #[test]
+    fn zero_components() {
+        for bytes in [hex!("3006020100020101"), hex!("3006020101020100")] {
+            let result = Signature::deserialize(&bytes[..]);
+            assert!(matches!(result, Err(Error::InvalidSignature(_))));
+        }
+
+        assert!(Signature::deserialize(&hex!("3006020101020101")[..]).is_ok());
+    }
+
+    #[test]
+    fn sighash_roundtrip() {
+        let r = biguint!("ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f");
+        let s = biguint!("7a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed");
+        let signature = Signature::new(r, s);
+
+        let serialized = signature.serialize_with_sighash(0x01).unwrap();
+        assert_eq!(serialized.last(), Some(&0x01));
+        assert_eq!(
+            &serialized[..serialized.len() - 1],
+            signature.serialize().unwrap().as_slice()
+        );
+
+        let (deserialized, sighash) = Signature::deserialize_with_sighash(&serialized).unwrap();
+        assert_eq!(deserialized, signature);
+        assert_eq!(sighash, 0x01);
+
+        let der = signature.serialize().unwrap();
+        assert!(Signature::deserialize_with_sighash(&der).is_err());
+    }
+
+    #[test]
+    fn compact_roundtrip() {
+        // small r to check the padding
+        let r = biguint!("00006a0610995c58074999cb9767b87af4c4978db68c06e8e6e81d282047a7c6");
+        let s = biguint!("8ca63759c1157ebeaec0d03cecca119fc9a75bf8e6d0fa65c841c8e2738cdaec");
+        let signature = Signature::new(r, s);
+
+        let compact = signature.to_compact().unwrap();
+        assert_eq!(
+            compact[..],
+            hex!(
+                "00006a0610995c58074999cb9767b87af4c4978db68c06e8e6e81d282047a7c6
+                8ca63759c1157ebeaec0d03cecca119fc9a75bf8e6d0fa65c841c8e2738cdaec"
+            )[..]
+        );
+
+        assert_eq!(
+            Signature::from_bytes_be(&compact[..32], &compact[32..]).unwrap(),
+            signature
+        );
+
+        let mut r_le = compact[..32].to_vec();
+        let mut s_le = compact[32..].to_vec();
+        r_le.reverse();
+        s_le.reverse();
+        assert_eq!(Signature::from_bytes_le(r_le, s_le).unwrap(), signature);
+
+        // zero and N (or larger) components are rejected
+        let n = prepend_padding(N.to_bytes_be(), 32, 0).unwrap();
+        for (r, s) in [([0u8; 32], [1u8; 32]), ([1u8; 32], [0u8; 32])] {
+            assert!(Signature::from_bytes_be(r, s).is_err());
+            assert!(Signature::from_bytes_le(r, s).is_err());
+        }
+        assert!(Signature::from_bytes_be(&n[..], &[1u8; 32][..]).is_err());
+        assert!(Signature::from_bytes_be(&[1u8; 32][..], &[0xff; 32][..]).is_err());
+
+        // zero values built directly still serialize
+        let zero = Signature::new(0usize, 1usize);
+        assert_eq!(zero.serialize().unwrap(), hex!("3006020100020101"));
+        assert_eq!(&zero.to_compact().unwrap()[..32], &[0u8; 32]);
+    }
 }
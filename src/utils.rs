@@ -1,6 +1,7 @@
 use std::cmp::Ordering;
+use std::io::{self, Read};
 
-use hmac::{Hmac, Mac};
+use hmac::Mac;
 use ripemd160::Ripemd160;
 use sha2::{Digest, Sha256};
 
@@ -41,6 +42,34 @@ where
     &arr[new_start..]
 }
 
+/// Read a 256-bit value serialized in little-endian, returning its big-endian
+/// bytes (i.e. the order used to display hashes and transaction ids).
+pub(crate) fn read_u256_le(mut reader: impl Read) -> io::Result<[u8; 32]> {
+    let mut bytes = [0u8; 32];
+    reader.read_exact(&mut bytes)?;
+    bytes.reverse();
+    Ok(bytes)
+}
+
+/// Get the little-endian serialization of a 256-bit value given in big-endian,
+/// this is the inverse of [`read_u256_le`].
+pub(crate) fn write_u256_le(value: &[u8; 32]) -> [u8; 32] {
+    let mut bytes = *value;
+    bytes.reverse();
+    bytes
+}
+
+/// Get the bytes in reverse order, e.g. to convert a hash between its internal
+/// order and the one used to display it (as in transaction ids)
+pub fn reverse_bytes(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().rev().copied().collect()
+}
+
+/// Reverse the byte order of a hex string, see [`reverse_bytes`]
+pub fn reverse_hex(string: &str) -> Result<String> {
+    Ok(hex::encode(reverse_bytes(&hex::decode(string)?)))
+}
+
 pub fn hash160<B>(data: B) -> Vec<u8>
 where
     B: AsRef<[u8]>,
@@ -51,7 +80,7 @@ where
     let hasher = Ripemd160::new();
     let digest = hasher.chain(digest).finalize();
 
-    digest.as_slice().to_vec()
+    digest.to_vec()
 }
 
 pub fn hash256<B>(data: B) -> Vec<u8>
@@ -66,14 +95,56 @@ where
     hasher.update(digest);
     let digest = hasher.finalize();
 
-    digest.as_slice().to_vec()
+    digest.to_vec()
 }
 
+/// Feed the whole `reader` into the given hasher, without buffering it all in
+/// memory.
+fn digest_reader<D, R>(mut hasher: D, reader: &mut R) -> io::Result<D>
+where
+    D: Digest,
+    R: Read,
+{
+    let mut buf = [0u8; 4096];
+
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(hasher),
+            Ok(read) => hasher.update(&buf[..read]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Same as [`hash160`], but reading the data incrementally from `reader`.
+pub fn hash160_reader<R: Read>(reader: &mut R) -> io::Result<[u8; 20]> {
+    let digest = digest_reader(Sha256::new(), reader)?.finalize();
+
+    let hasher = Ripemd160::new();
+    let digest = hasher.chain(digest).finalize();
+
+    Ok(digest.into())
+}
+
+/// Same as [`hash256`], but reading the data incrementally from `reader`.
+pub fn hash256_reader<R: Read>(reader: &mut R) -> io::Result<[u8; 32]> {
+    let mut hasher = digest_reader(Sha256::new(), reader)?;
+    let digest = hasher.finalize_reset();
+
+    hasher.update(digest);
+    let digest = hasher.finalize();
+
+    Ok(digest.into())
+}
+
+/// Feed data to a MAC by value, so updates can be chained (e.g. HMAC-SHA256
+/// for RFC6979 nonces or HMAC-SHA512 for BIP32 keys)
 pub(crate) trait Chain {
     fn chain(self, data: &[u8]) -> Self;
 }
 
-impl Chain for Hmac<Sha256> {
+impl<M: Mac> Chain for M {
     fn chain(mut self, data: &[u8]) -> Self {
         self.update(data);
         self
@@ -83,3 +154,79 @@ impl Chain for Hmac<Sha256> {
 pub(crate) fn default<T: Default>() -> T {
     Default::default()
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use hex_literal::hex;
+
+    use super::*;
+
+    #[test]
+    fn chain_hmac_sha512() -> Result<()> {
+        use hmac::{Hmac, NewMac};
+        use sha2::Sha512;
+
+        // RFC4231 test case 2
+        let hmac = Hmac::<Sha512>::new_varkey(b"Jefe").unwrap();
+        let tag = hmac
+            .chain(b"what do ya ")
+            .chain(b"want for nothing?")
+            .finalize()
+            .into_bytes();
+
+        assert_eq!(
+            tag[..],
+            hex!(
+                "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a99
+                4a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"
+            )[..]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn reverse_txid() -> Result<()> {
+        let txid = "452c629d67e41baec3ac6f04fe744b4b9617f8f859c63b3002f8684e7a4fee03";
+        let internal = "03ee4f7a4e68f802303bc659f8f817964b4b74fe046facc3ae1be4679d622c45";
+
+        assert_eq!(reverse_hex(txid)?, internal);
+        assert_eq!(reverse_hex(internal)?, txid);
+        assert_eq!(reverse_bytes(&hex::decode(txid)?), hex::decode(internal)?);
+        assert!(reverse_hex("abc").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn u256_le_roundtrip() -> Result<()> {
+        // previous tx as displayed and as found in the serialized input
+        let prev_tx = hex!("d1c789a9c60383bf715f3f6ad9d14b91fe55f3deb369fe5d9280cb1a01793f81");
+        let serialized = hex!("813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1");
+
+        assert_eq!(write_u256_le(&prev_tx), serialized);
+        assert_eq!(read_u256_le(&serialized[..])?, prev_tx);
+
+        Ok(())
+    }
+
+    #[test]
+    fn hash_reader_matches_in_memory() -> Result<()> {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+
+        let digest = hash256_reader(&mut data.as_slice())?;
+        assert_eq!(digest.to_vec(), hash256(&data));
+
+        let digest = hash160_reader(&mut data.as_slice())?;
+        assert_eq!(digest.to_vec(), hash160(&data));
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_u256_le_too_short() {
+        let bytes = [0u8; 31];
+        assert!(read_u256_le(&bytes[..]).is_err());
+    }
+}
@@ -1,4 +1,6 @@
-use std::convert::TryFrom;
+use std::cmp::Ordering;
+use std::convert::{TryFrom, TryInto};
+use std::fmt::{self, Display, Formatter};
 
 use byteorder::{LittleEndian, ReadBytesExt};
 use bytes::Buf;
@@ -63,17 +65,14 @@ impl TryFrom<u64> for VarInt {
     }
 }
 
-#[cfg(target_pointer_width = "16")]
-impl From<usize> for VarInt {
-    fn from(val: usize) -> Self {
-        From::<u16>::from(val as u16)
-    }
-}
+// Narrow targets also implement `TryFrom` (instead of `From`) so `?` can be
+// used the same way everywhere, widening to `u32` is always lossless there
+#[cfg(any(target_pointer_width = "16", target_pointer_width = "32"))]
+impl TryFrom<usize> for VarInt {
+    type Error = Error;
 
-#[cfg(target_pointer_width = "32")]
-impl From<usize> for VarInt {
-    fn from(val: usize) -> Self {
-        From::<u32>::from(val as u32)
+    fn try_from(val: usize) -> Result<Self, Self::Error> {
+        Ok(From::<u32>::from(val as u32))
     }
 }
 
@@ -108,17 +107,17 @@ impl VarInt {
         let mut reader = bytes.reader();
 
         match reader.read_u8()? {
-            first if first == 0xfd => {
+            0xfd => {
                 let value = reader.read_u16::<LittleEndian>()?;
                 Ok(Self::U16(value))
             }
 
-            first if first == 0xfe => {
+            0xfe => {
                 let value = reader.read_u32::<LittleEndian>()?;
                 Ok(Self::U32(value))
             }
 
-            first if first == 0xff => {
+            0xff => {
                 let value = reader.read_u64::<LittleEndian>()?;
                 Ok(Self::U64(value))
             }
@@ -127,6 +126,25 @@ impl VarInt {
         }
     }
 
+    /// Decode a varint from the start of a plain slice, returning it along
+    /// with the number of bytes consumed
+    pub fn from_slice(bytes: &[u8]) -> Result<(Self, usize)> {
+        fn le_bytes<const N: usize>(bytes: &[u8]) -> Result<[u8; N]> {
+            bytes
+                .get(1..=N)
+                .map(|bytes| bytes.try_into().unwrap()) // safe
+                .ok_or(Error::InvalidBytesForVarInt)
+        }
+
+        match bytes.first() {
+            Some(0xfd) => Ok((Self::U16(u16::from_le_bytes(le_bytes(bytes)?)), 3)),
+            Some(0xfe) => Ok((Self::U32(u32::from_le_bytes(le_bytes(bytes)?)), 5)),
+            Some(0xff) => Ok((Self::U64(u64::from_le_bytes(le_bytes(bytes)?)), 9)),
+            Some(value) => Ok((Self::U8(*value), 1)),
+            None => Err(Error::InvalidBytesForVarInt),
+        }
+    }
+
     pub fn as_u64(self) -> u64 {
         match self {
             VarInt::U8(val) => val as u64,
@@ -137,6 +155,35 @@ impl VarInt {
     }
 }
 
+impl Display for VarInt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_u64())
+    }
+}
+
+/// Varints are ordered by value, the same value with a wider (non canonical)
+/// encoding is ordered after so this stays consistent with `Eq`
+impl Ord for VarInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let width = |varint: &VarInt| match varint {
+            VarInt::U8(_) => 0,
+            VarInt::U16(_) => 1,
+            VarInt::U32(_) => 2,
+            VarInt::U64(_) => 3,
+        };
+
+        self.as_u64()
+            .cmp(&other.as_u64())
+            .then_with(|| width(self).cmp(&width(other)))
+    }
+}
+
+impl PartialOrd for VarInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
@@ -233,4 +280,98 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn u8_u16_boundary() -> Result<()> {
+        for &(val, expected) in &[
+            (252u32, VarInt::U8(252)),
+            (253, VarInt::U16(253)),
+            (254, VarInt::U16(254)),
+        ] {
+            assert_eq!(VarInt::from(val as u16), expected);
+            assert_eq!(VarInt::from(val), expected);
+            assert_eq!(VarInt::try_from(val as u64)?, expected);
+            assert_eq!(VarInt::try_from(val as usize)?, expected);
+        }
+
+        assert_eq!(VarInt::from(252u8).serialize(), vec![0xfc]);
+        assert_eq!(VarInt::from(253u8).serialize(), vec![0xfd, 0xfd, 0x00]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn usize_varint() -> Result<()> {
+        // values representable on every target
+        for &val in &[0usize, 252, 253, 0xfffe, 0xffff] {
+            let expected = VarInt::from(val as u16);
+            assert_eq!(VarInt::try_from(val)?, expected);
+            assert_eq!(VarInt::try_from(val)?.as_u64(), val as u64);
+        }
+
+        Ok(())
+    }
+
+    #[cfg(any(target_pointer_width = "32", target_pointer_width = "64"))]
+    #[test]
+    fn wide_usize_varint() -> Result<()> {
+        for &val in &[0x1_0000usize, 0xffff_fffe, 0xffff_ffff] {
+            assert_eq!(VarInt::try_from(val)?, VarInt::from(val as u32));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_slice() -> Result<()> {
+        let encodings: [&[u8]; 4] = [
+            &[0xea],
+            &[0xfd, 0x49, 0x85],
+            &[0xfe, 0x05, 0xd8, 0xff, 0xff],
+            &[0xff, 0x8d, 0x46, 0xdc, 0xff, 0xff, 0xff, 0xff, 0xff],
+        ];
+
+        for encoded in &encodings {
+            let mut bytes = encoded.to_vec();
+            bytes.push(0xaa); // trailing bytes aren't consumed
+
+            let (varint, consumed) = VarInt::from_slice(&bytes)?;
+            assert_eq!(varint, VarInt::deserialize(*encoded)?);
+            assert_eq!(consumed, encoded.len());
+        }
+
+        assert!(VarInt::from_slice(&[]).is_err());
+        assert!(VarInt::from_slice(&[0xfe, 0x05, 0xd8]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn display_and_order() {
+        let mut varints = vec![
+            VarInt::U64(1 << 40),
+            VarInt::U16(300),
+            VarInt::U8(7),
+            VarInt::U32(70000),
+            VarInt::U16(7),
+            VarInt::U8(0),
+        ];
+        varints.sort();
+
+        assert_eq!(
+            varints,
+            [
+                VarInt::U8(0),
+                VarInt::U8(7),
+                VarInt::U16(7),
+                VarInt::U16(300),
+                VarInt::U32(70000),
+                VarInt::U64(1 << 40),
+            ]
+        );
+        assert!(VarInt::U8(200) < VarInt::U16(300));
+
+        let printed: Vec<_> = varints.iter().map(ToString::to_string).collect();
+        assert_eq!(printed, ["0", "7", "7", "300", "70000", "1099511627776"]);
+    }
 }
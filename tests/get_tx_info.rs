@@ -0,0 +1,130 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use anyhow::Result;
+use oxicoin::core::fetcher::FETCHER_URL_VAR;
+
+// echo <tx hex> | get_tx_info
+#[test]
+fn reads_tx_from_stdin() -> Result<()> {
+    let tx_hex = "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d10000\
+                  00006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457\
+                  c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed012103\
+                  49fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135\
+                  ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000\
+                  001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600";
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_get_tx_info"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    writeln!(child.stdin.take().unwrap(), "{}", tx_hex)?;
+    let output = child.wait_with_output()?;
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.contains("Id: "));
+    assert!(stdout.contains("Inputs: 1\n"));
+    assert!(stdout.contains("Outputs: 2\n"));
+    assert!(!stdout.contains("Fee: "));
+
+    Ok(())
+}
+
+#[test]
+fn rejects_invalid_hex() -> Result<()> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_get_tx_info"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    writeln!(child.stdin.take().unwrap(), "not hex")?;
+    assert!(!child.wait()?.success());
+
+    Ok(())
+}
+
+// Previous transaction (id 452c629d...) served by the mock server
+const PREV_TX_HEX: &str = "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1\
+                           000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320\
+                           b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10\
+                           615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b27\
+                           8afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9a\
+                           da88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac\
+                           19430600";
+
+// Spends the first output (32454049 sats) of the previous transaction into a
+// single output of 32450000 sats
+const SPENDING_TX_HEX: &str =
+    "010000000103ee4f7a4e68f802303bc659f8f817964b4b74fe046facc3ae1be4679d622c45\
+     0000000000ffffffff01d025ef01000000000000000000";
+
+/// Serve a single request with `response`, returning the base url
+fn mock_server(response: String) -> Result<(String, thread::JoinHandle<std::io::Result<String>>)> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}", listener.local_addr()?);
+
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept()?;
+        let mut request = [0u8; 1024];
+        let read = stream.read(&mut request)?;
+        stream.write_all(response.as_bytes())?;
+        Ok(String::from_utf8_lossy(&request[..read]).into_owned())
+    });
+
+    Ok((url, server))
+}
+
+fn run_with_fee(url: &str) -> Result<std::process::Output> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_get_tx_info"))
+        .arg("--fee")
+        .env(FETCHER_URL_VAR, url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    writeln!(child.stdin.take().unwrap(), "{}", SPENDING_TX_HEX)?;
+    Ok(child.wait_with_output()?)
+}
+
+#[test]
+fn fee_from_fetched_tx() -> Result<()> {
+    let body = format!("{}\n", PREV_TX_HEX);
+    let response = format!(
+        "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    let (url, server) = mock_server(response)?;
+
+    let output = run_with_fee(&url)?;
+    let request = server.join().unwrap()?;
+    assert!(request.starts_with(
+        "GET /tx/452c629d67e41baec3ac6f04fe744b4b9617f8f859c63b3002f8684e7a4fee03.hex "
+    ));
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.contains("Fee: 4049\n"));
+
+    Ok(())
+}
+
+#[test]
+fn fee_fetch_failure() -> Result<()> {
+    let response = "HTTP/1.1 404 Not Found\r\ncontent-length: 9\r\n\r\nnot found".to_string();
+    let (url, server) = mock_server(response)?;
+
+    let output = run_with_fee(&url)?;
+    server.join().unwrap()?;
+
+    assert!(!output.status.success());
+    assert!(!String::from_utf8(output.stdout)?.contains("Fee: "));
+
+    Ok(())
+}
@@ -1,10 +1,18 @@
+use std::convert::TryFrom;
+
 use anyhow::Result;
 use hex_literal::hex;
 use num_bigint::BigUint;
 use oxicoin::biguint;
-use oxicoin::secp256k1::crypto::{PrivateKey, PublicKey};
+use oxicoin::network::Network;
+use oxicoin::secp256k1::crypto::{
+    address_from_sec, addresses_for_secrets, KeyPair, PrivateKey, PublicKey,
+};
 use oxicoin::secp256k1::curve::Point;
-use oxicoin::secp256k1::signature::Signature;
+use oxicoin::secp256k1::signature::{recover_private_key, verify_batch, RecoveryId, Signature};
+use oxicoin::secp256k1::{generator, order};
+use oxicoin::utils::hash256;
+use oxicoin::Error;
 
 #[test]
 fn signature_must_be_valid() -> Result<()> {
@@ -18,30 +26,235 @@ fn signature_must_be_valid() -> Result<()> {
     let y = biguint!("82b51eab8c27c66e26c858a079bcdf4f1ada34cec420cafc7eac1a42216fb6c4");
     let pub_key = PublicKey::new(x, y)?;
 
-    assert!(signature.is_valid(&digest, &pub_key)?);
+    assert!(signature.is_valid(digest, &pub_key)?);
     Ok(())
 }
 
 #[test]
 fn create_and_validate_signature() -> Result<()> {
-    let privkey = PrivateKey::new(BigUint::from(12345usize));
+    let privkey = PrivateKey::new(BigUint::from(12345usize))?;
     let digest = hex!("bc62d4b80d9e36da29c16c5d4d9f11731f36052c72401a76c23c0fb5a9b74423");
 
-    let signature = privkey.create_signature(&digest)?;
+    let signature = privkey.create_signature(digest)?;
 
     insta::assert_debug_snapshot!(signature); // signature shouldn't change
     assert!(privkey
         .public_key()
-        .valid_signature(&digest, &signature)
+        .valid_signature(digest, &signature)
         .unwrap());
 
     Ok(())
 }
 
+#[test]
+fn signature_summing_to_infinity() -> Result<()> {
+    // with secret e = 1 and r = s = 1, u·G + v·P = (z + 1)·G, which is the
+    // point at infinity for z = N - 1
+    let private_key = PrivateKey::new(1usize)?;
+    let digest = hex!("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364140");
+    let signature = Signature::new(1usize, 1usize);
+
+    assert!(!signature.is_valid(digest, private_key.public_key())?);
+
+    Ok(())
+}
+
+#[test]
+fn low_r_signature() -> Result<()> {
+    let private_key = PrivateKey::new(12345usize)?;
+
+    // find a digest whose regular signature needs a padded r
+    let (digest, regular) = (0u8..)
+        .map(|i| {
+            let digest = [i; 32];
+            (digest, private_key.create_signature(digest).unwrap())
+        })
+        .find(|(_, signature)| signature.serialize().unwrap()[3] == 33)
+        .unwrap();
+
+    let low_r = private_key.create_signature_low_r(digest)?;
+    assert_ne!(low_r, regular);
+    assert_eq!(low_r.serialize()?[3], 32);
+    assert_eq!(low_r.serialize()?.len() + 1, regular.serialize()?.len());
+    assert!(private_key.public_key().valid_signature(digest, &low_r)?);
+
+    // deterministic
+    assert_eq!(private_key.create_signature_low_r(digest)?, low_r);
+
+    Ok(())
+}
+
+#[test]
+fn secret_out_of_range() {
+    let result = PrivateKey::new(0usize);
+    assert!(matches!(result, Err(Error::InvalidSecret)));
+
+    let result = PrivateKey::new(order().clone());
+    assert!(matches!(result, Err(Error::InvalidSecret)));
+
+    assert!(PrivateKey::new(order() - 1usize).is_ok());
+    assert!(PrivateKey::from_bytes_be([0u8; 32]).is_err());
+}
+
+#[test]
+fn secret_from_slice() -> Result<()> {
+    let mut secret = [0u8; 32];
+    secret[31] = 1;
+    assert_eq!(PrivateKey::from_slice(&secret)?, PrivateKey::new(1usize)?);
+
+    for length in [0, 20, 31, 33, 40].iter() {
+        let result = PrivateKey::from_slice(&vec![0x01; *length]);
+        assert!(matches!(result, Err(Error::InvalidSecretLength(l)) if l == *length));
+    }
+
+    // the lenient constructor still takes any length
+    assert!(PrivateKey::from_bytes_be([0x01; 20]).is_ok());
+
+    let result = PrivateKey::from_slice(&[0u8; 32]);
+    assert!(matches!(result, Err(Error::InvalidSecret)));
+    let result = PrivateKey::from_slice(&order().to_bytes_be());
+    assert!(matches!(result, Err(Error::InvalidSecret)));
+    let result = PrivateKey::from_slice(&[0xff; 32]);
+    assert!(matches!(result, Err(Error::InvalidSecret)));
+
+    Ok(())
+}
+
+#[test]
+fn secret_to_bytes() -> Result<()> {
+    let key = PrivateKey::new(5002usize)?;
+    let mut expected = [0u8; 32];
+    expected[30..].copy_from_slice(&[0x13, 0x8a]);
+    assert_eq!(key.to_bytes(), expected);
+    assert_eq!(PrivateKey::from_bytes_be(key.to_bytes())?, key);
+
+    let key = PrivateKey::new(order() - 1usize)?;
+    assert_eq!(PrivateKey::from_slice(&key.to_bytes())?, key);
+
+    Ok(())
+}
+
+#[test]
+fn verify_message() -> Result<()> {
+    let private_key = PrivateKey::new(8675309usize)?;
+    let message = b"Programming Bitcoin!";
+    let digest = hash256(message);
+    let der_sig = private_key.create_signature(&digest)?.serialize()?;
+
+    let public_key = private_key.public_key();
+    assert!(public_key.verify(message, &der_sig)?);
+    assert!(!public_key.verify(b"Programming Bitcoin?", &der_sig)?);
+    assert!(!PrivateKey::new(1usize)?
+        .public_key()
+        .verify(message, &der_sig)?);
+    assert!(public_key.verify(message, &der_sig[1..]).is_err());
+
+    // malformed signatures are errors, not panics
+    for malformed in [
+        &[0x30, 0xfe, 0x02, 0x00][..],
+        &[0x30, 0xff, 0x02, 0x00, 0x00],
+        &[],
+    ] {
+        assert!(public_key.verify(message, malformed).is_err());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn generator_times_order() -> Result<()> {
+    assert!(generator().mul_scalar(order()).is_point_at_inf());
+
+    let private_key = PrivateKey::new(12345usize)?;
+    let product = generator().mul_scalar(&BigUint::from(12345usize));
+    assert_eq!(PublicKey::from(product), *private_key.public_key());
+
+    Ok(())
+}
+
+#[test]
+fn public_key_hash160() -> Result<()> {
+    let sec = hex!("0349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278a");
+    let public_key = PublicKey::deserialize(sec)?;
+
+    assert_eq!(
+        public_key.hash160(true)?,
+        hex!("a802fc56c704ce87c42d7c92eb75e7896bdc41ae")
+    );
+    assert_ne!(public_key.hash160(false)?, public_key.hash160(true)?);
+
+    Ok(())
+}
+
+#[test]
+fn key_pair_address_and_wif() -> Result<()> {
+    let key_pair = KeyPair::new(5002usize)?;
+
+    let address = key_pair.address(false, Network::Testnet)?;
+    assert_eq!(address, "mmTPbXQFxboEtNRkwfh6K51jvdtHLxGeMA");
+
+    let wif = key_pair.wif(false, Network::Testnet)?;
+    assert_eq!(wif, "91avARGdfge8E4tZfYLoxeJ5sGBdNJQH4kvjJoQFacbrg3CnTu9");
+    assert_eq!(KeyPair::from_wif(&wif)?, key_pair);
+
+    let digest = hex!("bc62d4b80d9e36da29c16c5d4d9f11731f36052c72401a76c23c0fb5a9b74423");
+    let signature = key_pair.sign(digest)?;
+    assert!(key_pair.public_key().valid_signature(digest, &signature)?);
+
+    Ok(())
+}
+
+#[test]
+fn address_from_sec_bytes() -> Result<()> {
+    let sec = hex!("0349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278a");
+    let address = address_from_sec(&sec, true, false)?;
+    assert_eq!(address, "1GKN6gJBgvet8S92qiQjVxEaVJ5eoJE9s2");
+    assert_eq!(
+        address,
+        PublicKey::deserialize(sec)?.create_address(true, false)?
+    );
+
+    let private_key = PrivateKey::new(5002usize)?;
+    let sec = private_key.public_key().serialize(false)?;
+    assert_eq!(
+        address_from_sec(&sec, false, true)?,
+        "mmTPbXQFxboEtNRkwfh6K51jvdtHLxGeMA"
+    );
+
+    assert!(address_from_sec(&sec, true, true).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn batch_verification() -> Result<()> {
+    let digests = [[0x11u8; 32], [0x22; 32], [0x33; 32], [0x44; 32]];
+
+    let mut items = Vec::new();
+    for (secret, digest) in (1000usize..).zip(digests.iter()) {
+        let private_key = PrivateKey::new(secret)?;
+        let signature = private_key.create_signature(digest)?;
+        items.push((signature, *digest, private_key.public_key().clone()));
+    }
+
+    // signature over another digest
+    items[2].1 = [0x55; 32];
+
+    let sequential: Vec<_> = items
+        .iter()
+        .map(|(signature, digest, pub_key)| signature.is_valid(digest, pub_key).unwrap())
+        .collect();
+
+    assert_eq!(verify_batch(&items), sequential);
+    assert_eq!(sequential, vec![true, true, false, true]);
+
+    Ok(())
+}
+
 #[test]
 fn uncompressed_sec_format() {
     fn test_case(secret: usize, expected: &[u8]) {
-        let private_key = PrivateKey::new(secret);
+        let private_key = PrivateKey::new(secret).unwrap();
         let public_key = private_key.public_key();
         let serialized = public_key.serialize(false).unwrap();
 
@@ -78,7 +291,7 @@ fn uncompressed_sec_format() {
 #[test]
 fn compressed_sec_serialization() {
     fn test_case(secret: usize, expected: &[u8]) {
-        let private_key = PrivateKey::new(secret);
+        let private_key = PrivateKey::new(secret).unwrap();
         let public_key = private_key.public_key();
         let serialized = public_key.serialize(true).unwrap();
 
@@ -103,14 +316,62 @@ fn compressed_sec_serialization() {
     );
 }
 
+#[test]
+fn public_key_try_from_bytes() {
+    let private_key = PrivateKey::new(5001usize).unwrap();
+    let public_key = private_key.public_key();
+
+    let compressed = public_key.serialize(true).unwrap();
+    let parsed = PublicKey::try_from(compressed.as_slice()).unwrap();
+    assert_eq!(&parsed, public_key);
+
+    let uncompressed = public_key.serialize(false).unwrap();
+    let parsed = PublicKey::try_from(uncompressed.as_slice()).unwrap();
+    assert_eq!(&parsed, public_key);
+
+    let result = PublicKey::try_from(&compressed[..32]);
+    assert!(matches!(result, Err(Error::InvalidSecBytesLength(32))));
+}
+
+#[test]
+fn sec_format_is_remembered() {
+    let private_key = PrivateKey::new(5000usize).unwrap();
+    let public_key = private_key.public_key();
+    assert!(public_key.is_compressed());
+
+    let uncompressed = public_key.serialize(false).unwrap();
+    let parsed = PublicKey::deserialize(&uncompressed).unwrap();
+    assert!(!parsed.is_compressed());
+    assert_eq!(parsed.to_sec().unwrap(), uncompressed);
+    assert_eq!(&parsed, public_key);
+
+    let compressed = public_key.serialize(true).unwrap();
+    let parsed = PublicKey::deserialize(&compressed).unwrap();
+    assert!(parsed.is_compressed());
+    assert_eq!(parsed.to_sec().unwrap(), compressed);
+}
+
+#[test]
+fn public_key_validity() {
+    let public_key = PublicKey::from(Point::at_infinity());
+    assert!(!public_key.is_valid());
+
+    let private_key = PrivateKey::new(5002usize).unwrap();
+    assert!(private_key.public_key().is_valid());
+}
+
 #[test]
 fn address_creation() {
     fn test_case(secret: usize, compressed: bool, testnet: bool, expected: &str) {
-        let private_key = PrivateKey::new(secret);
+        let private_key = PrivateKey::new(secret).unwrap();
         let public_key = private_key.public_key();
         let address = public_key.create_address(compressed, testnet).unwrap();
 
         assert_eq!(expected, address);
+
+        // the other SEC form hashes to a different address
+        let other = public_key.create_address(!compressed, testnet).unwrap();
+        assert_ne!(other, address);
     }
 
     test_case(5002, false, true, "mmTPbXQFxboEtNRkwfh6K51jvdtHLxGeMA");
@@ -128,10 +389,27 @@ fn address_creation() {
     );
 }
 
+#[test]
+fn regtest_prefixes() {
+    let private_key = PrivateKey::new(5002usize).unwrap();
+    let public_key = private_key.public_key();
+
+    let address = public_key.address(false, Network::Regtest).unwrap();
+    assert_eq!(address, "mmTPbXQFxboEtNRkwfh6K51jvdtHLxGeMA");
+    assert_eq!(address, public_key.create_address(false, true).unwrap());
+
+    let address = public_key.address(true, Network::Signet).unwrap();
+    assert!(address.starts_with('m') || address.starts_with('n'));
+
+    let wif = private_key.wif(true, Network::Regtest).unwrap();
+    assert!(wif.starts_with('c'));
+    assert_eq!(wif, private_key.create_wif(true, true).unwrap());
+}
+
 #[test]
 fn create_wif() {
     fn test_case(secret: usize, compressed: bool, testnet: bool, expected: &str) {
-        let private_key = PrivateKey::new(secret);
+        let private_key = PrivateKey::new(secret).unwrap();
         let wif = private_key.create_wif(compressed, testnet).unwrap();
 
         assert_eq!(expected, wif);
@@ -156,3 +434,158 @@ fn create_wif() {
         "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgiuQJv1h8Ytr2S53a",
     );
 }
+
+#[test]
+fn parse_wif() {
+    fn test_case(secret: usize, compressed: bool, testnet: bool) {
+        let private_key = PrivateKey::new(secret).unwrap();
+        let wif = private_key.create_wif(compressed, testnet).unwrap();
+
+        assert_eq!(PrivateKey::from_wif(&wif).unwrap(), private_key);
+    }
+
+    test_case(5003, true, true);
+    test_case(33715652388894101, false, true);
+    test_case(1481187632463599, true, false);
+
+    assert!(PrivateKey::from_wif("mmTPbXQFxboEtNRkwfh6K51jvdtHLxGeMA").is_err());
+}
+
+#[test]
+fn addresses_for_secret_range() -> Result<()> {
+    let secrets = (1usize..=3).map(BigUint::from);
+    let addresses: Vec<_> =
+        addresses_for_secrets(secrets, true, Network::Mainnet).collect::<Result<_, _>>()?;
+    assert_eq!(
+        addresses,
+        [
+            "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH",
+            "1cMh228HTCiwS8ZsaakH8A8wze1JR5ZsP",
+            "1CUNEBjYrCn2y1SdiUMohaKUi4wpP326Lb",
+        ]
+    );
+
+    let secrets = (1usize..=3).map(BigUint::from);
+    let addresses: Vec<_> =
+        addresses_for_secrets(secrets, false, Network::Testnet).collect::<Result<_, _>>()?;
+    assert_eq!(
+        addresses,
+        [
+            "mtoKs9V381UAhUia3d7Vb9GNak8Qvmcsme",
+            "n16daMq14zdjocUU5rUDVxts8449SL2VrL",
+            "n35Rg6P9RAk1YiQPX2tVcQtt8ta1bZ36Z1",
+        ]
+    );
+
+    let mut addresses = addresses_for_secrets(
+        vec![BigUint::from(0usize), BigUint::from(1usize)].into_iter(),
+        true,
+        Network::Mainnet,
+    );
+    assert!(addresses.next().unwrap().is_err());
+    assert!(addresses.next().unwrap().is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn nonce_reuse_recovery() -> Result<()> {
+    let private_key = PrivateKey::new(biguint!(
+        "8f8a276c19f4149656b280621e358cce24f5f52542772691ee69063b74f15d15"
+    ))?;
+    let k = biguint!("38aa22d72376b4dbc472e06c3ba403ee0a394da63fc58d88686c611aba98d6b3");
+
+    let z1 = [0x11u8; 32];
+    let z2 = [0x22u8; 32];
+    let sig1 = private_key.create_signature_with_k(z1, &k)?;
+    let sig2 = private_key.create_signature_with_k(z2, &k)?;
+    assert!(sig1.shares_nonce(&sig2));
+
+    let secret = recover_private_key(&sig1, z1, &sig2, z2).unwrap();
+    assert_eq!(PrivateKey::new(secret)?, private_key);
+
+    // different nonces don't leak anything
+    let sig3 = private_key.create_signature(z2)?;
+    assert!(!sig1.shares_nonce(&sig3));
+    assert!(recover_private_key(&sig1, z1, &sig3, z2).is_none());
+
+    Ok(())
+}
+
+#[test]
+fn recoverable_signatures() -> Result<()> {
+    for id in 0..4 {
+        let recovery_id = RecoveryId::new(id)?;
+        let (y_is_odd, x_overflow) = (recovery_id.y_is_odd(), recovery_id.x_overflow());
+        assert_eq!(RecoveryId::from_parts(y_is_odd, x_overflow), recovery_id);
+        assert_eq!(recovery_id.to_u8(), id);
+    }
+    assert!(RecoveryId::new(4).is_err());
+
+    let private_key = PrivateKey::new(8675309usize)?;
+    let public_key = private_key.public_key();
+
+    let mut parities = [false; 2];
+    for i in 0..16u8 {
+        let digest = [i; 32];
+        let (signature, recovery_id) = private_key.create_recoverable_signature(digest)?;
+        assert_eq!(signature, private_key.create_signature(digest)?);
+        assert!(!recovery_id.x_overflow());
+        parities[recovery_id.y_is_odd() as usize] = true;
+
+        assert_eq!(&signature.recover(digest, recovery_id)?, public_key);
+
+        // any other id gives a different key (or none at all)
+        for id in (0..4).filter(|id| *id != recovery_id.to_u8()) {
+            let other = signature.recover(digest, RecoveryId::new(id)?);
+            assert!(!matches!(other, Ok(key) if &key == public_key));
+        }
+    }
+
+    assert_eq!(parities, [true, true]);
+
+    Ok(())
+}
+
+#[test]
+fn signature_components() -> Result<()> {
+    let r = biguint!("37206a0610995c58074999cb9767b87af4c4978db68c06e8e6e81d282047a7c6");
+    let s = biguint!("8ca63759c1157ebeaec0d03cecca119fc9a75bf8e6d0fa65c841c8e2738cdaec");
+    let signature = Signature::new(r.clone(), s.clone());
+    assert_eq!(signature.r(), &r);
+    assert_eq!(signature.s(), &s);
+
+    // signatures made by this crate are low-s
+    let signature = PrivateKey::new(8675309usize)?.create_signature([0x01; 32])?;
+    assert!(signature.s() <= &(order() / 2usize));
+
+    Ok(())
+}
+
+#[test]
+fn wif_compression_roundtrip() -> Result<()> {
+    let key_pair = KeyPair::new(33632321603200000u64)?;
+    let compressed_wif = key_pair.wif(true, Network::Testnet)?;
+    let uncompressed_wif = key_pair.wif(false, Network::Testnet)?;
+
+    let imported = KeyPair::from_wif(&compressed_wif)?;
+    assert!(imported.is_compressed());
+    assert_eq!(
+        imported.preferred_address(Network::Testnet)?,
+        "mopVkxp8UhXqRYbCYJsbeE1h1fiF64jcoH"
+    );
+    assert_eq!(imported.preferred_wif(Network::Testnet)?, compressed_wif);
+
+    let imported = KeyPair::from_wif(&uncompressed_wif)?;
+    assert!(!imported.is_compressed());
+    assert_eq!(
+        imported.preferred_address(Network::Testnet)?,
+        key_pair.address(false, Network::Testnet)?
+    );
+    assert_eq!(imported.preferred_wif(Network::Testnet)?, uncompressed_wif);
+
+    let overridden = imported.with_compressed(true);
+    assert_eq!(overridden.preferred_wif(Network::Testnet)?, compressed_wif);
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+//! Deterministic (RFC6979) signing vectors for secp256k1 with low-s
+//! normalization, as produced by libsecp256k1. The signatures and public keys
+//! were also checked against OpenSSL.
+
+use anyhow::Result;
+use num_bigint::BigUint;
+use oxicoin::secp256k1::crypto::{PrivateKey, PublicKey};
+use oxicoin::secp256k1::signature::Signature;
+
+struct Vector {
+    secret: &'static str,
+    digest: &'static str,
+    k: &'static str,
+    signature: &'static str,
+    public_key: &'static str,
+}
+
+const VECTORS: &[Vector] = &[
+    // secret = 1, digest = sha256("Satoshi Nakamoto")
+    Vector {
+        secret: "0000000000000000000000000000000000000000000000000000000000000001",
+        digest: "a0dc65ffca799873cbea0ac274015b9526505daaaed385155425f7337704883e",
+        k: "8f8a276c19f4149656b280621e358cce24f5f52542772691ee69063b74f15d15",
+        signature: "3045022100934b1ea10a4b3c1757e2b0c017d0b6143ce3c9a7e6a4a49860d7a6ab210ee3d802202442ce9d2b916064108014783e923ec36b49743e2ffa1c4496f01a512aafd9e5",
+        public_key: "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+    },
+    Vector {
+        secret: "0000000000000000000000000000000000000000000000000000000000000001",
+        digest: "7d1833f54854ac51659521afcd0ec6dca2ce2351429614bfa28a756b1b3c637f",
+        k: "38aa22d72376b4dbc472e06c3ba403ee0a394da63fc58d88686c611aba98d6b3",
+        signature: "30450221008600dbd41e348fe5c9465ab92d23e3db8b98b873beecd930736488696438cb6b0220547fe64427496db33bf66019dacbf0039c04199abb0122918601db38a72cfc21",
+        public_key: "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+    },
+    // secret = N - 1
+    Vector {
+        secret: "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364140",
+        digest: "a0dc65ffca799873cbea0ac274015b9526505daaaed385155425f7337704883e",
+        k: "33a19b60e25fb6f4435af53a3d42d493644827367e6453928554f43e49aa6f90",
+        signature: "3045022100fd567d121db66e382991534ada77a6bd3106f0a1098c231e47993447cd6af2d002206b39cd0eb1bc8603e159ef5c20a5c8ad685a45b06ce9bebed3f153d10d93bed5",
+        public_key: "0379be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+    },
+    Vector {
+        secret: "f8b8af8ce3c7cca5e300d33939540c10d45ce001b8f252bfbc57ba0342904181",
+        digest: "4ba38d48a60f1b29e9eb726eaff08b2e83d8d81e031666fee50e85900d7dc1ef",
+        k: "525a82b70e67874398067543fd84c83d30c175fdc45fdeee082fe13b1d7cfdf1",
+        signature: "304402207063ae83e7f62bbb171798131b4a0564b956930092b33b07b395615d9ec7e15c022058dfcc1e00a35e1572f366ffe34ba0fc47db1e7189759b9fb233c5b05ab388ea",
+        public_key: "0292df7b245b81aa637ab4e867c8d511008f79161a97d64f2ac709600352f7acbc",
+    },
+    Vector {
+        secret: "e91671c46231f833a6406ccbea0e3e392c76c167bac1cb013f6f1013980455c2",
+        digest: "1609a53bb33ef00e0cc1e784b436d7924956d87ec2b399574378312f07cba3e8",
+        k: "1f4b84c23a86a221d233f2521be018d9318639d5b8bbd6374a8a59232d16ad3d",
+        signature: "3045022100b552edd27580141f3b2a5463048cb7cd3e047b97c9f98076c32dbdf85a68718b0220279fa72dd19bfae05577e06c7c0c1900c371fcd5893f7e1d56a37d30174671f6",
+        public_key: "03567b7512001f3cc4dcb8b8096c046fff571ab07adb2126cd42908f2ff1ca424a",
+    },
+];
+
+#[test]
+fn rfc6979_vectors() -> Result<()> {
+    for vector in VECTORS {
+        let private_key = PrivateKey::from_bytes_be(hex::decode(vector.secret)?)?;
+        let digest = hex::decode(vector.digest)?;
+        let k = BigUint::from_bytes_be(&hex::decode(vector.k)?);
+        let expected = hex::decode(vector.signature)?;
+
+        let public_key = private_key.public_key();
+        assert_eq!(public_key.serialize(true)?, hex::decode(vector.public_key)?);
+        assert_eq!(
+            &PublicKey::deserialize(hex::decode(vector.public_key)?)?,
+            public_key
+        );
+
+        let signature = private_key.create_signature(&digest)?;
+        assert_eq!(signature.serialize()?, expected, "secret {}", vector.secret);
+        assert_eq!(private_key.create_signature_with_k(&digest, &k)?, signature);
+
+        let parsed = Signature::deserialize(expected.as_slice())?;
+        assert_eq!(parsed, signature);
+        assert!(parsed.is_valid(&digest, public_key)?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn nonce_out_of_range() {
+    let private_key = PrivateKey::new(1usize).unwrap();
+    let digest = [0x01u8; 32];
+
+    let n = BigUint::from_bytes_be(&hex::decode(VECTORS[2].secret).unwrap()) + 1usize;
+    assert!(private_key.create_signature_with_k(digest, &n).is_err());
+    assert!(private_key
+        .create_signature_with_k(digest, &BigUint::from(0usize))
+        .is_err());
+}
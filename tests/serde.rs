@@ -0,0 +1,87 @@
+#![cfg(feature = "serde")]
+
+use anyhow::Result;
+use hex_literal::hex;
+use oxicoin::secp256k1::crypto::{PrivateKey, PublicKey};
+use oxicoin::secp256k1::signature::Signature;
+
+#[test]
+fn signature_json_roundtrip() -> Result<()> {
+    let private_key = PrivateKey::new(12345usize)?;
+    let digest = hex!("bc62d4b80d9e36da29c16c5d4d9f11731f36052c72401a76c23c0fb5a9b74423");
+    let signature = private_key.create_signature(digest)?;
+
+    let json = serde_json::to_string(&signature)?;
+    assert_eq!(json, format!("\"{}\"", hex::encode(signature.serialize()?)));
+
+    let parsed: Signature = serde_json::from_str(&json)?;
+    assert_eq!(parsed, signature);
+
+    Ok(())
+}
+
+#[test]
+fn malformed_signature_json() {
+    let malformed = [
+        "\"30ff02\"",
+        "\"30fe020000\"",
+        "\"3006020100020101\"", // r = 0
+        "\"3006020101020100\"", // s = 0
+        "\"not hex\"",
+    ];
+
+    for json in malformed.iter() {
+        assert!(serde_json::from_str::<Signature>(json).is_err());
+    }
+}
+
+#[test]
+fn public_key_json_roundtrip() -> Result<()> {
+    let private_key = PrivateKey::new(5001usize)?;
+    let public_key = private_key.public_key();
+
+    let json = serde_json::to_string(public_key)?;
+    assert_eq!(
+        json,
+        "\"0357a4f368868a8a6d572991e484e664810ff14c05c0fa023275251151fe0e53d1\""
+    );
+
+    let parsed: PublicKey = serde_json::from_str(&json)?;
+    assert_eq!(&parsed, public_key);
+
+    Ok(())
+}
+
+#[test]
+fn private_key_json_roundtrip() -> Result<()> {
+    let private_key = PrivateKey::new(1481187632463599usize)?;
+
+    let json = serde_json::to_string(&private_key)?;
+    assert_eq!(
+        json,
+        "\"KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgiuQJv1h8Ytr2S53a\""
+    );
+
+    let parsed: PrivateKey = serde_json::from_str(&json)?;
+    assert_eq!(parsed, private_key);
+
+    assert!(serde_json::from_str::<PrivateKey>("\"not a wif\"").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn uncompressed_private_key_json_roundtrip() -> Result<()> {
+    let wif = PrivateKey::new(5003usize)?.create_wif(false, false)?;
+    let private_key = PrivateKey::from_wif(&wif)?;
+    assert!(!private_key.public_key().is_compressed());
+
+    let json = serde_json::to_string(&private_key)?;
+    assert_eq!(json, format!("\"{}\"", wif));
+
+    let parsed: PrivateKey = serde_json::from_str(&json)?;
+    assert_eq!(parsed, private_key);
+    assert!(!parsed.public_key().is_compressed());
+
+    Ok(())
+}
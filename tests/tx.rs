@@ -0,0 +1,80 @@
+use anyhow::Result;
+use hex_literal::hex;
+use oxicoin::core::tx::Tx;
+
+fn assert_roundtrip(bytes: &[u8], expected_id: &str) -> Result<()> {
+    let tx = Tx::deserialize(bytes, false)?;
+    assert_eq!(tx.serialize()?, bytes);
+
+    let mut written = Vec::new();
+    tx.write_to(&mut written)?;
+    assert_eq!(written, bytes);
+    assert_eq!(tx.id()?, expected_id);
+    assert_eq!(tx.txid()?, expected_id);
+    assert_eq!(tx.wtxid()?, expected_id);
+
+    // the testnet flag isn't part of the transaction
+    let reparsed = Tx::deserialize(tx.serialize()?.as_slice(), true)?;
+    assert_eq!(reparsed, tx);
+
+    Ok(())
+}
+
+#[test]
+fn legacy_mainnet_roundtrip() -> Result<()> {
+    let bytes = hex!(
+        "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b
+        483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a98
+        6d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545
+        de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b
+        654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e33216
+        6702cb75f40df79fea1288ac19430600"
+    );
+
+    assert_roundtrip(
+        &bytes,
+        "452c629d67e41baec3ac6f04fe744b4b9617f8f859c63b3002f8684e7a4fee03",
+    )
+}
+
+#[test]
+fn genesis_coinbase_roundtrip() -> Result<()> {
+    let bytes = hex!(
+        "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04
+        ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e
+        206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f205
+        2a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6
+        bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000"
+    );
+
+    assert_roundtrip(
+        &bytes,
+        "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+    )
+}
+
+#[test]
+fn strict_deserialization() -> Result<()> {
+    let bytes = hex!(
+        "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04
+        ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e
+        206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f205
+        2a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6
+        bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000"
+    );
+
+    let tx = Tx::deserialize_strict(&bytes, false)?;
+    assert_eq!(tx, Tx::deserialize(&bytes[..], false)?);
+
+    // trailing garbage is only caught by the strict version
+    let mut trailing = bytes.to_vec();
+    trailing.extend_from_slice(&[0xde, 0xad]);
+    assert_eq!(Tx::deserialize(trailing.as_slice(), false)?, tx);
+    assert!(Tx::deserialize_strict(&trailing, false).is_err());
+
+    for length in 0..bytes.len() {
+        assert!(Tx::deserialize_strict(&bytes[..length], false).is_err());
+    }
+
+    Ok(())
+}